@@ -12,8 +12,13 @@ $(document).ready(function(){
       // http://www.redotheweb.com/2013/05/15/client-side-full-text-search-in-css.html
       // may not scale well?
       var search_html = "";
-      // replace "div c" with "div-c", and like, for the data-search attribute
-      let words = search_text.replace(/(div|division) ([abc])/, "$1-$2");
+      // replace "div c" with "div-c", "level states" with "level-states",
+      // "size large" with "size-large" and "tag mini" with "tag-mini", and
+      // like, for the data-search attribute
+      let words = search_text.replace(/(div|division) ([abc])/, "$1-$2")
+                             .replace(/level (\w+)/, "level-$1")
+                             .replace(/size (\w+)/, "size-$1")
+                             .replace(/tag ([\w-]+)/, "tag-$1");
       words.split(/\s+/).forEach(function(word) { // split on whitespace
         search_html += "div.card:not([data-search*=\"" + word + "\"])" +
                        "{ display: none; }\n";
@@ -26,8 +31,13 @@ $(document).ready(function(){
     localStorage.setItem('searchstyle', search_html);
   });
 
-  // Restore search bar status if exists
-  if(localStorage.getItem('searchstring')) {
+  // Search for ?q= if given (e.g. from the search box on the 404 page), and
+  // otherwise restore search bar status if exists
+  let query = new URLSearchParams(window.location.search).get("q");
+  if (query) {
+    $("div.search-wrapper input").val(query).trigger("input");
+    $("div.search-wrapper div.floating-label").addClass("has-value");
+  } else if(localStorage.getItem('searchstring')) {
     $("div.search-wrapper input").val(localStorage.getItem('searchstring'));
     $("style#search_style").html(localStorage.getItem('searchstyle'));
     $("div.search-wrapper div.floating-label").addClass("has-value");
@@ -40,6 +50,38 @@ $(document).ready(function(){
     $("input#searchTournaments").blur();
   });
 
+  // Sort tournaments by date (the order they come in), by page views, by
+  // number of teams, or by strength of field, with fields measured by
+  // performance index first, then those measured by attendance, then those
+  // without any history
+  var sort_cards = function(key) {
+    let grid = $("div.results-index-card-grid");
+    let basis_rank = { "performance_index": 0, "appearances": 1, "": 2 };
+    grid.children("div.card").sort(function(a, b) {
+      if (key === "strength") {
+        let by_basis = basis_rank[a.dataset.strengthBasis] -
+                       basis_rank[b.dataset.strengthBasis];
+        if (by_basis !== 0) return by_basis;
+        let by_value = (b.dataset.strength || 0) - (a.dataset.strength || 0);
+        if (by_value !== 0) return by_value;
+      } else if (key !== "order") {
+        let by_count = (b.dataset[key] || 0) - (a.dataset[key] || 0);
+        if (by_count !== 0) return by_count;
+      }
+      return a.dataset.order - b.dataset.order;
+    }).appendTo(grid);
+    localStorage.setItem('sortkey', key);
+  };
+  $("select#sortTournaments").on("change", function() {
+    sort_cards($(this).val());
+  });
+  let saved_key = localStorage.getItem('sortkey');
+  if (saved_key && saved_key !== "order" &&
+      $("select#sortTournaments option[value='" + saved_key + "']").length) {
+    $("select#sortTournaments").val(saved_key);
+    sort_cards(saved_key);
+  }
+
   // Prevent see all from appending anchor tag to URL (makes the back button
   // logic more consistent)
   $("a#see-all").on("click", function(e) {
@@ -109,6 +151,12 @@ $(document).ready(function(){
     lazy_images.each(function() {
       lazyImageObserver.observe(this);
     });
+  } else {
+    // leave it to the browser, since the images have loading="lazy"
+    lazy_images.each(function() {
+      this.src = this.dataset.src;
+      this.classList.remove("lazy");
+    });
   }
 
   // Disabled for now (may try to find a way to enable for PWAs only?) because
@@ -339,7 +339,7 @@ $(document).ready(function(){
     $("div#team-detail span#place").html(getOrdinal(place));
     $("div#team-detail span#team").html(source_row.attr("data-team-name"));
     $("div#team-detail span#school").html(source_row.attr("data-school"));
-    let h = "schools.html#" + source_row.attr("data-school").replace(/ /g, "_");
+    let h = "schools.html#" + source_row.attr("data-school-anchor");
     if (window.location.href.startsWith("https://unosmium.org")) {
       h = h.replace(".html", "");
     }
@@ -1,65 +1,1331 @@
 # frozen_string_literal: true
 
+require 'csv'
+require 'digest'
+require 'fastimage'
+require 'json'
+require 'openssl'
+require 'set'
 require 'sciolyff/interpreter'
+require 'yaml'
+require_relative 'helpers/custom_helpers'
+require_relative 'scripts/validate-exports'
+
+build_started = Process.clock_gettime(Process::CLOCK_MONOTONIC)
+
+# Build settings are all environment variables (see MAINTAINERS.md) -- the ones
+# that take a value are checked before anything is built, so that a typo fails
+# right away rather than partway through, and with CONFIG_CHECK set the
+# resolved settings are printed instead of building
+settings = {
+  'MIN_BUILD'            => [nil, /\A\d*\z/, 'a number of tournaments'],
+  'OFFLINE_ARCHIVE_TOP'  => ['50', /\A\d+\z/, 'a whole number'],
+  'OFFLINE_ARCHIVE_MAX'  => ['5000000', /\A\d+\z/, 'a number of bytes'],
+  'SUMMARY_MIN_TEAMS'    => ['60', /\A\d+\z/, 'a whole number'],
+  'SUMMARY_TOP'          => ['15', /\A\d+\z/, 'a whole number'],
+  'DATE_TOLERANCE_DAYS'  => ['0', /\A\d+\z/, 'a whole number'],
+  'SEASON_CUTOFF'        => ['08-01', /\A\d\d-\d\d\z/, 'a MM-DD date'],
+  'LOCALES'              => [nil, /\A[a-z]{2}(,[a-z]{2})*\z/,
+                             'comma-separated language codes'],
+  'WARNINGS_JSON'        => [nil, /./, 'a file path'],
+  'PERFORMANCE_METHOD'   => ['percentile', /\A(percentile|zscore)\z/,
+                             'percentile or zscore'],
+  'PERFORMANCE_MIN'      => ['2', /\A\d+\z/, 'a whole number'],
+  'EXTRA_RESULTS'        => [nil, /\A[^=,]+=[^,]+(,[^=,]+=[^,]+)*\z/,
+                             'comma-separated label=directory pairs'],
+  'CSV_NULL'             => [nil, /\A[^\s,"]+\z/,
+                             'a value without spaces, commas or quotes'],
+  'CORRECTIONS_IGNORE'   => [nil, /./, 'a commit subject prefix'],
+  'PRELIMINARY_DAYS'     => ['14', /\A\d+\z/, 'a whole number'],
+  'THEME_DRIFT'          => ['25', /\A\d+(\.\d+)?\z/, 'a number'],
+  'SIGNING_KEY'          => [nil, /./,
+                             'a file path (needs openssl gem 3.0+)'],
+  'METRICS_CSV'          => [nil, /./, 'a file path'],
+  'SIZE_BUCKETS'         => ['20,45', /\A\d+,\d+\z/,
+                             'two team counts, e.g. 20,45'],
+  'ALL_TIME_MIN'         => ['3', /\A\d+\z/, 'a whole number'],
+  'FEED_MIN'             => ['3', /\A\d+\z/, 'a whole number'],
+  'FEED_LOOKBACK'        => ['2', /\A\d+\z/, 'a whole number']
+}
+settings.each do |name, (_, format, description)|
+  next if ENV[name].nil? || ENV[name].match?(format)
+
+  raise ArgumentError,
+        "#{name} must be #{description}, not #{ENV[name].inspect}"
+end
+ENV['LOCALES'].to_s.split(',').each do |locale|
+  next if File.exist?(File.join(__dir__, 'data', 'locales', "#{locale}.yaml"))
+
+  raise ArgumentError,
+        "LOCALES has #{locale}, but data/locales/#{locale}.yaml does not exist"
+end
+if ENV['SEASON_CUTOFF'] &&
+   !Date.valid_date?(2000, *ENV['SEASON_CUTOFF'].split('-').map(&:to_i))
+  raise ArgumentError, "SEASON_CUTOFF #{ENV['SEASON_CUTOFF']} is not a date"
+end
+# reading and signing with Ed25519 keys only arrived in version 3.0 of the
+# openssl gem (the one that comes with Ruby 3.1), and older ones fail on the key
+# with a less helpful "unsupported key type"
+if ENV['SIGNING_KEY'] &&
+   Gem::Version.new(OpenSSL::VERSION) < Gem::Version.new('3.0')
+  raise ArgumentError, 'SIGNING_KEY needs version 3.0 or later of the openssl '\
+                       "gem (Ruby 3.1 or later), not #{OpenSSL::VERSION}"
+end
+if ENV['CONFIG_CHECK']
+  flags = %w[INDEX_ONLY EXPORTS_ONLY PAGES_ONLY INCLUDE_IGNORED
+             INCLUDE_TRIAL_MEDALS OFFLINE_ARCHIVE LOGO_REPORT STRICT_A11Y
+             STRICT_LOGOS REQUIRE_ATTRIBUTION CSV_BOM CSV_QUOTE_ALL CSV_TSV
+             REPRODUCIBLE_BUILD HEATMAPS SCORE_HISTOGRAMS
+             ALLOW_RENDER_FALLBACK]
+  settings.each do |name, (default, _, _)|
+    puts "#{name}=#{ENV[name] || default}#{' (default)' unless ENV[name]}"
+  end
+  flags.each { |name| puts "#{name}=#{ENV[name] ? 'on' : 'off'}" }
+  exit
+end
 
 ignore '/results/placeholder.html'
+ignore '/results/fallback.html'
+ignore '/results/schools/feed.ics'
+ignore 'images/media/*/captions.yaml'
 ignore '/results/template.html'
+ignore '/results/template.csv'
+ignore '/results/attendance.html'
+ignore '/results/attendance.csv'
+ignore '/results/summary.html'
+ignore '/results/team-numbers.csv'
+ignore '/results/team-numbers.json'
+ignore '/results/calendar.html'
+ignore '/results/all-time.html'
+ignore '/results/all-time.csv'
+ignore '/results/tag.html'
+ignore '/results/widgets/leaderboard.html'
+ignore '/results/archive.html' unless ENV['OFFLINE_ARCHIVE']
+ignore '/results/logo-report.html' unless ENV['LOGO_REPORT']
+
+# Only the CSV and JSON exports, or only the pages, can be rebuilt with
+# EXPORTS_ONLY or PAGES_ONLY (together with --no-clean, so that the rest of an
+# earlier build is kept) -- the PWA manifest counts as part of the pages
+ignore(/\.html\z/) if ENV['EXPORTS_ONLY']
+if ENV['PAGES_ONLY']
+  ignore(%r{results/(?!manifest\.json\z).*\.(csv|(geo)?json)\z})
+end
+
+# strip trailing whitespace from CSV files
+after_build do |builder|
+  Dir[File.join(config[:build_dir], 'results', '**', '*.csv')].each do |path|
+    builder.thor.gsub_file path, /\s+\Z/, ''
+  end
+end
+
+# tab-separated copies of every CSV export, read back through the CSV parser so
+# that they hold exactly the same fields
+if ENV['CSV_TSV']
+  after_build do
+    Dir[File.join(config[:build_dir], 'results', '**', '*.csv')].each do |path|
+      text = File.read(path, encoding: 'bom|utf-8')
+      options = { col_sep: "\t", force_quotes: !ENV['CSV_QUOTE_ALL'].nil? }
+      tsv = CSV.generate(**options) do |out|
+        CSV.parse(text) { |row| out << row }
+      end
+      File.write(path.sub(/\.csv\z/, '.tsv'), tsv)
+    end
+  end
+end
 
 if (num = ENV['MIN_BUILD'])
   ignore '/results/index.html'
   ignore '/results/schools.html'
+  ignore '/results/performance.html'
   ignore '/results/schools.csv'
   ignore '/results/events.csv'
+  ignore '/results/events.html'
+  ignore '/results/events_by_division.csv'
+  ignore '/results/events_annotated.csv'
+  ignore '/results/trophies.json'
+  ignore '/results/records.html'
+  ignore '/results/records.json'
+  ignore '/results/schools.json'
+  ignore '/results/schools/summary.json'
+  ignore '/results/rankings.json'
+  ignore '/results/rankings.csv'
+  ignore '/results/podiums.json'
+  ignore '/results/corrections.json'
+  ignore '/results/attributions.html'
+  ignore '/results/locations.geojson'
+  ignore '/404.html'
+  ignore '/results/archive.html'
+  ignore '/results/logo-report.html'
   num = num.empty? ? 1 : num.to_i
   @app.data.recents[0...num].each do |recent|
     filename = recent.delete_suffix('.yaml').to_sym
+    tournament = @app.data.to_h[filename]
+    interpreter = SciolyFF::Interpreter.new(tournament)
     proxy "/results/#{filename}.html",
           '/results/template.html',
-          locals: { i: SciolyFF::Interpreter.new(@app.data.to_h[filename]) }
+          locals: { i: interpreter, ext: tournament[:unosmium] || {},
+                    summary: false, filename: filename.to_s,
+                    location: nil, media: [] }
+    proxy "/results/#{filename}.csv",
+          '/results/template.csv',
+          locals: { i: interpreter }
   end
   return
 end
 
+# Problems found while reading logos and results files are logged, and with
+# WARNINGS_JSON set also written to that path as JSON (e.g. for turning into CI
+# annotations), each identified by one of these codes
+warning_codes = {
+  'invalid-logo-name' => 'Logo file name is not valid UTF-8, so it is skipped',
+  'ignored-results'   => 'Results file is left out because of .resultsignore',
+  'missing-file-date' => 'Results file name does not start with a date',
+  'date-mismatch'     => 'Results file name date differs from tournament date',
+  'season-mismatch'   => 'Tournament year is not the season its date falls in',
+  'unknown-key'       => 'Results file has a top-level key SciolyFF ignores',
+  'empty-results'     => 'Results file has no events or teams and is left out',
+  'logo-size'         => 'Logo is too small to be legible or needlessly large',
+  'animated-logo'     => 'Logo is animated, which is distracting on the page',
+  'unused-logo'       => 'Logo file is not picked for any tournament',
+  'default-logo'      => 'Tournaments that fall back to the default logo',
+  'unmatched-views'   => 'View counts file has a path that is not a tournament',
+  'duplicate-results' => 'Results file has the same name as one already read',
+  'bad-source-url'    => 'Results file provenance source_url is not a web URL',
+  'duplicate-number'  => 'Results file has more than one team with a number',
+  'bad-priority'      => 'Results file index priority is not a whole number',
+  'stale-preliminary' => 'Preliminary results file has not changed in a while',
+  'no-attribution'    => 'Logo used in the build has no attribution entry',
+  'stale-attribution' => 'Logo attribution entry is for a logo that is gone',
+  'theme-drift'       => 'Theme color was darkened far from the logo color',
+  'bad-prelims'       => 'Results file prelims is not another results file',
+  'unlinked-prelims'  => 'Preliminary-looking results file has no finals link',
+  'bad-slug'          => 'Results file slug is not usable as a page name',
+  'unknown-location'  => 'Locations file has a name that is not a tournament',
+  'unsafe-page-name'  => 'Results file name is not a valid Windows file name',
+  'page-name-clash'   => 'Page names differ only in case, which Windows merges',
+  'render-failed'     => 'SciolyFF failed on a results file, so it gets a stub',
+  'unofficial-event'  => 'Event is not listed as official in its division then',
+  'unseen-official'   => 'Official event is in no results file of its division',
+  'bad-upcoming'      => 'Upcoming tournament has no name, link or date',
+  'school-slug-clash' => 'School name gives the same anchor as another school',
+  'shallow-history'   => 'Repository is a shallow clone, so history is partial',
+  'unsupported-media' => 'Media file is not a JPEG, PNG or WebP image',
+  'missing-thumbnail' => 'Media image has no thumbnail from scripts/media.sh',
+  'results-bom'       => 'Results file starts with a byte order mark',
+  'legacy-encoding'   => 'Results file is not UTF-8, so it was transcoded'
+}.freeze
+warnings = []
+add_warning = lambda do |code, file, message, severity: 'warning', **context|
+  unless warning_codes.key?(code)
+    raise ArgumentError, "undocumented warning code #{code}"
+  end
+
+  warnings << { severity: severity, code: code, file: file,
+                message: message, context: context }
+  @app.logger.public_send(severity == 'info' ? :info : :warn,
+                          "#{file}: #{message}")
+end
+
+# Logos with file names that aren't valid UTF-8 (e.g. from a bad rename) can't
+# be matched to a tournament, so find_logo_path skips them
+logos_dir = File.join(@app.root, 'source', 'images', 'logos')
+Dir.children(logos_dir).reject(&:valid_encoding?).each do |image|
+  add_warning.call('invalid-logo-name', "source/images/logos/#{image.scrub}",
+                   'skipping logo with invalid file name')
+end
+
+# Logos are shown at card size, so thumbnails end up blurry, while huge scans
+# only make the site heavier -- limits are on the longest side in pixels (SVGs
+# by their canvas size) and on the file size in bytes
+logo_limits = { min_side: 200, max_side: 4000, max_bytes: 2 * 1024 * 1024 }
+helper_methods = Object.new.extend(CustomHelpers)
+Dir.children(logos_dir).select(&:valid_encoding?).sort.each do |image|
+  path = File.join(logos_dir, image)
+  width, height = FastImage.size(path)
+  next unless width
+
+  unless path.end_with?('.ico') || helper_methods.logo_frames(path).size < 2
+    add_warning.call('animated-logo', "source/images/logos/#{image}",
+                     'logo is animated, its first frame is used for the theme '\
+                     'color')
+  end
+
+  bytes = File.size(path)
+  next if [width, height].max.between?(logo_limits[:min_side],
+                                       logo_limits[:max_side]) &&
+          bytes <= logo_limits[:max_bytes]
+
+  add_warning.call('logo-size', "source/images/logos/#{image}",
+                   "logo is #{width}x#{height} px and #{bytes} bytes",
+                   width: width, height: height, bytes: bytes)
+end
+
+# Once every page has picked its logo, report logos that no tournament uses any
+# more (e.g. after a results file was renamed) and tournaments still using the
+# default logo, grouped by tournament -- with STRICT_LOGOS set, unused logos
+# fail the build. Logos are only picked while pages render, so none of this is
+# reported when only some pages are built (EXPORTS_ONLY and INDEX_ONLY, and
+# MIN_BUILD, which never gets this far), since every logo would look unused.
+pages_rendered = !ENV['EXPORTS_ONLY'] && !ENV['INDEX_ONLY']
+after_build do
+  next unless pages_rendered
+
+  unused = Dir.children(logos_dir).select(&:valid_encoding?).sort -
+           ['default.jpg'] - CustomHelpers::USED_LOGOS.to_a
+  unused.each do |image|
+    add_warning.call('unused-logo', "source/images/logos/#{image}",
+                     'logo is not used by any tournament')
+  end
+  CustomHelpers::DEFAULT_LOGO_TOURNAMENTS
+    .group_by { |filename| filename[11..-3] }.sort.each do |name, filenames|
+    add_warning.call('default-logo', 'source/images/logos',
+                     "no #{name} logo for #{filenames.sort.join(', ')}",
+                     severity: 'info', files: filenames.sort)
+  end
+  CustomHelpers::DRIFTED_THEME_COLORS.sort.each do |filename, drift|
+    add_warning.call('theme-drift', "data/#{filename}.yaml",
+                     "theme color #{drift[:color]} is #{drift[:drift]} away "\
+                     "from logo color #{drift[:source]}",
+                     severity: 'info', **drift)
+  end
+  if ENV['STRICT_LOGOS'] && unused.any?
+    raise "#{unused.size} unused logo(s): #{unused.join(', ')}"
+  end
+end
+
+# Renamed events in data/event_lineage.yaml -- if two names in a lineage, or one
+# name in two lineages, overlap in seasons, it would be ambiguous which lineage
+# an event is in, so the build stops
+lineages = YAML.safe_load(File.read(File.join(@app.root, 'data',
+                                              'event_lineage.yaml'))) || {}
+lineage_names = lineages.flat_map do |id, names|
+  Array(names).map do |entry|
+    from, to = entry.values_at('from', 'to') if entry.is_a?(Hash)
+    unless from.is_a?(Integer) && entry['name'] &&
+           (to.nil? || (to.is_a?(Integer) && from <= to))
+      raise ArgumentError,
+            "event lineage #{id} has a bad entry #{entry.inspect}"
+    end
+
+    [id, entry['name'], from, to || Float::INFINITY]
+  end
+end
+lineage_names.combination(2).each do |a, b|
+  next unless a[0] == b[0] || a[1] == b[1]
+  next if a[3] < b[2] || b[3] < a[2]
+
+  raise ArgumentError, "event lineage #{a[0]} (#{a[1]}) overlaps "\
+                       "#{b[0]} (#{b[1]}) in data/event_lineage.yaml"
+end
+
+# Logo attributions in data/logo_attributions.yaml for logos that no longer
+# exist, and with REQUIRE_ATTRIBUTION set, logos used without an attribution
+attributions = @app.data.logo_attributions.to_h
+(attributions.keys.map(&:to_s) - Dir.children(logos_dir)).sort.each do |image|
+  add_warning.call('stale-attribution', 'data/logo_attributions.yaml',
+                   "attribution for missing logo #{image}", logo: image)
+end
+if ENV['REQUIRE_ATTRIBUTION'] && pages_rendered
+  after_build do
+    (CustomHelpers::USED_LOGOS.to_a - attributions.keys.map(&:to_s))
+      .sort.each do |image|
+      add_warning.call('no-attribution', "source/images/logos/#{image}",
+                       'logo has no entry in data/logo_attributions.yaml')
+    end
+  end
+end
+
+if ENV['WARNINGS_JSON']
+  after_build do
+    File.write(ENV['WARNINGS_JSON'], JSON.pretty_generate(warnings))
+  end
+end
+
 # Proxy pages
 # https://middlemanapp.com/advanced/dynamic-pages/
 
+# Results files on a branch that aren't ready to be published can be left out
+# of the build by listing gitignore-style glob patterns (matched against file
+# names in data/) in .resultsignore, unless INCLUDE_IGNORED is set
+ignore_file = File.join(@app.root, '.resultsignore')
+ignore_patterns = if File.exist?(ignore_file) && !ENV['INCLUDE_IGNORED']
+                    File.readlines(ignore_file, chomp: true)
+                        .map(&:strip)
+                        .reject { |l| l.empty? || l.start_with?('#') }
+                  else
+                    []
+                  end
+results_ignored = lambda do |file|
+  ignore_patterns.reduce(false) do |ignored, pattern|
+    negated = pattern.start_with?('!')
+    pattern = pattern.delete_prefix('!')
+    File.fnmatch?(pattern, file, File::FNM_EXTGLOB) ? !negated : ignored
+  end
+end
+
+# results files are named starting with the tournament date, e.g.
+# 2019-06-01_nationals_c, which is also used to pick the logo for that year
+filename_date = lambda do |filename|
+  Date.iso8601(filename[/^\d{4}-\d{2}-\d{2}/].to_s)
+rescue ArgumentError
+  nil
+end
+
+# how many days the file name date may be off from the tournament date before
+# it's warned about (e.g. 1 for files named after the first day of a two-day
+# tournament), with the tournament date being the one shown on the site
+date_tolerance = (ENV['DATE_TOLERANCE_DAYS'] || 0).to_i
+
+# SciolyFF doesn't complain about top-level keys it doesn't know (e.g. a typo
+# like `Penalty:`), it just never reads them
+known_keys = %i[Tournament Subdivisions Events Teams Placings Penalties
+                unosmium].freeze
+
+# Seasons run from August 1 (or SEASON_CUTOFF, as MM-DD) to the following
+# summer and are named by the year they end in, which SciolyFF calls the
+# tournament year -- a December invitational belongs to next year's season
+season_cutoff = (ENV['SEASON_CUTOFF'] || '08-01').split('-').map(&:to_i)
+season_of = lambda do |date|
+  ([date.month, date.day] <=> season_cutoff) >= 0 ? date.year + 1 : date.year
+end
+
 interpreters = {}
 
-@app.data.to_h.each do |filename, tournament|
-  next unless filename.to_s.start_with?(/[0-9]/)
+# Results files SciolyFF fails on, which only get a plain fallback page
+fallbacks = {}
+
+# Empty results files kept with allow_empty, which are left out of everything
+# but a page of their notes
+empties = {}
+
+# Curator-maintained extras stored under an optional top-level `unosmium` key in
+# a results file, which SciolyFF ignores (e.g. notes shown as a page banner)
+extensions = Hash.new({})
+
+# Results can also be read from directories outside this repository (e.g. a
+# partner's own results repository), listed in EXTRA_RESULTS as label=directory
+# pairs -- their pages credit the label, and a file named the same as one
+# already read is skipped
+results_files = @app.data.to_h.select { |f, _| f.to_s.start_with?(/[0-9]/) }
+                    .map { |f, t| [f.to_s, t, "data/#{f}.yaml", nil] }
+
+# Results files saved from spreadsheet programs on Windows can start with a
+# byte order mark or be in Windows-1252 rather than UTF-8, which either fails
+# to parse or garbles school names -- the mark is stripped, a file that isn't
+# valid UTF-8 (or UTF-16 with a mark) is read as Windows-1252, and both get a
+# warning so that the file itself is fixed
+boms = { "\xEF\xBB\xBF".b => Encoding::UTF_8,
+         "\xFF\xFE".b => Encoding::UTF_16LE,
+         "\xFE\xFF".b => Encoding::UTF_16BE }
+needs_decoding = lambda do |path|
+  bytes = File.binread(path)
+  boms.keys.any? { |bom| bytes.start_with?(bom) } ||
+    !bytes.force_encoding(Encoding::UTF_8).valid_encoding?
+end
+read_results = lambda do |path, file|
+  bytes = File.binread(path)
+  bom, encoding = boms.find { |mark, _| bytes.start_with?(mark) }
+  if bom
+    bytes = bytes.byteslice(bom.bytesize..)
+    add_warning.call('results-bom', file, 'stripped a byte order mark',
+                     encoding: encoding.name)
+  end
+  text = bytes.dup.force_encoding(encoding || Encoding::UTF_8)
+  unless text.valid_encoding?
+    encoding = Encoding::Windows_1252
+    text = bytes.dup.force_encoding(encoding)
+    add_warning.call('legacy-encoding', file,
+                     'not valid UTF-8, read as Windows-1252 instead -- check '\
+                     'the school names and save the file as UTF-8',
+                     encoding: encoding.name)
+  end
+  text = text.encode(Encoding::UTF_8, invalid: :replace, undef: :replace)
+  YAML.safe_load(text, permitted_classes: [Date], symbolize_names: true)
+end
+Dir[File.join(@app.root, 'data', '[0-9]*.yaml')].sort.each do |path|
+  next unless needs_decoding.call(path)
+
+  filename = File.basename(path, '.yaml')
+  file = "data/#{filename}.yaml"
+  entry = [filename, read_results.call(path, file), file, nil]
+  index = results_files.index { |f, *| f == filename }
+  if index then results_files[index] = entry
+  else          results_files << entry
+  end
+end
+extra_files = {}
+ENV['EXTRA_RESULTS'].to_s.split(',').each do |source|
+  label, dir = source.split('=', 2)
+  Dir[File.join(File.expand_path(dir, @app.root), '*.yaml')].sort.each do |path|
+    filename = File.basename(path, '.yaml')
+    if results_files.any? { |f, *| f == filename }
+      add_warning.call('duplicate-results', path,
+                       "#{filename} was already read, skipping", source: label)
+      next
+    end
+
+    tournament = read_results.call(path, path)
+    results_files << [filename, tournament, path, label]
+    extra_files[filename] = path
+  end
+end
 
-  interpreters[filename.to_s] = SciolyFF::Interpreter.new(tournament)
+# the download links on results pages point at the files under data/
+after_build do
+  extra_files.each do |filename, path|
+    FileUtils.cp(path,
+                 File.join(config[:build_dir], 'data', "#{filename}.yaml"))
+  end
 end
 
-interpreters = interpreters.sort_by do |_, i|
+results_files.each do |filename, tournament, file, source|
+  if results_ignored.call("#{filename}.yaml")
+    add_warning.call('ignored-results', file, 'ignored by .resultsignore',
+                     severity: 'info')
+    next
+  end
+
+  (tournament.keys.map(&:to_sym) - known_keys).each do |key|
+    path = File.expand_path(file, @app.root)
+    line = File.exist?(path) &&
+           File.foreach(path).find_index { |l| l.start_with?("#{key}:") }
+    add_warning.call('unknown-key', file, "unknown top-level key #{key}",
+                     key: key.to_s, line: line ? line + 1 : nil)
+  end
+
+  # placeholder files with no events or teams would only make an empty page,
+  # unless the curator allows it (e.g. for a cancelled tournament with notes)
+  if tournament[:Events].to_a.empty? || tournament[:Teams].to_a.empty?
+    if (tournament[:unosmium] || {})[:allow_empty]
+      empties[filename] = tournament
+    else
+      add_warning.call('empty-results', file, 'skipped (empty)',
+                       severity: 'info')
+    end
+    next
+  end
+
+  # SciolyFF works out points and ranks lazily, so a results file it can't
+  # handle would only fail once its page is rendered, stopping the whole build
+  # -- they're worked out here instead, and a file that fails is left out of
+  # everything but a plain page of what can be read straight from it
+  begin
+    interpreter = SciolyFF::Interpreter.new(tournament)
+    interpreter.teams.each(&:rank).each(&:points)
+    interpreter.events.flat_map(&:placings).each(&:points)
+  rescue StandardError => e
+    add_warning.call('render-failed', file,
+                     "SciolyFF failed (#{e.class}: #{e.message}), using a "\
+                     'fallback page', severity: 'error', error: e.class.name)
+    fallbacks[filename] = tournament
+    next
+  end
+
+  file_date = filename_date.call(filename)
+  if file_date.nil?
+    add_warning.call('missing-file-date', file,
+                     'file name does not start with a date')
+  elsif (file_date - interpreter.tournament.date).abs > date_tolerance
+    add_warning.call('date-mismatch', file,
+                     "file name date #{file_date} does not match "\
+                     "tournament date #{interpreter.tournament.date}, "\
+                     'rename the file',
+                     file_date: file_date.iso8601,
+                     tournament_date: interpreter.tournament.date.iso8601)
+  end
+
+  season = season_of.call(interpreter.tournament.date)
+  if season != interpreter.tournament.year
+    add_warning.call('season-mismatch', file,
+                     "tournament year #{interpreter.tournament.year} but "\
+                     "date #{interpreter.tournament.date} is in the "\
+                     "#{season} season",
+                     year: interpreter.tournament.year, season: season)
+  end
+
+  interpreter.teams.group_by(&:number).each do |number, teams|
+    next if teams.size == 1
+
+    add_warning.call('duplicate-number', file,
+                     "#{teams.size} teams have number #{number}",
+                     number: number)
+  end
+
+  source_url = (tournament[:unosmium] || {})[:source_url]
+  if source_url && !source_url.to_s.match?(%r{\Ahttps?://[^/\s]+\S*\z})
+    add_warning.call('bad-source-url', file,
+                     "source_url #{source_url} is not an http(s) URL",
+                     source_url: source_url.to_s)
+  end
+
+  priority = (tournament[:unosmium] || {})[:priority]
+  unless priority.nil? || priority.is_a?(Integer)
+    add_warning.call('bad-priority', file,
+                     "priority #{priority} is not a whole number, so it is "\
+                     'ignored', priority: priority.to_s)
+  end
+
+  interpreters[filename] = interpreter
+  extensions[filename] = tournament[:unosmium] if tournament[:unosmium]
+  extensions[filename] = extensions[filename].merge(source: source) if source
+end
+
+# Tournaments held on the same date are listed with a higher `priority` (or
+# `pin: true`, which counts as priority 1) under `unosmium` first, e.g.
+# nationals ahead of an invitational that day -- the index stays in date order
+index_priority = lambda do |ext|
+  next ext[:priority] if ext[:priority].is_a?(Integer)
+
+  ext[:pin] ? 1 : 0
+end
+
+interpreters = interpreters.sort_by do |filename, i|
   [Date.new(2019, 10, 17) - i.tournament.date,
+   -index_priority.call(extensions[filename]),
    i.tournament.state,
    i.tournament.location,
    i.tournament.division]
 end.to_h
 
-page '/results/index.html', locals: { interpreters: interpreters }
-page '/results/schools.html', locals: { interpreters: interpreters }
-page '/results/schools.csv', locals: { interpreters: interpreters }
-page '/results/events.csv', locals: { interpreters: interpreters }
+# Every commit that changed a results file after the one that added it, read
+# from a single git log of data/, for /results/corrections.json -- commits whose
+# subject starts with CORRECTIONS_IGNORE (e.g. for formatting-only changes) are
+# left out, and so are files that were never corrected. The log is read newest
+# first with rename detection, so commits from before a file was renamed (e.g.
+# by scripts/migrate.rb) still count towards its current name, while the rename
+# itself doesn't unless the file was also changed. It's read with -z, since git
+# would otherwise quote paths with e.g. accented letters, and a shallow clone
+# (as CI services often make) only has part of the history, so it's warned
+# about.
+history = Hash.new { |h, filename| h[filename] = [] }
+begin
+  git = ['git', '-C', @app.root]
+  log = IO.popen([*git, 'log', '-z', '--name-status', '-M',
+                  '--format=%H%x09%cI%x09%s', '--', 'data'],
+                 err: File::NULL, &:read)
+  shallow = IO.popen([*git, 'rev-parse', '--is-shallow-repository'],
+                     err: File::NULL, &:read).strip == 'true'
+rescue SystemCallError
+  log = ''
+end
+if shallow
+  add_warning.call('shallow-history', 'data/',
+                   'the repository is a shallow clone, so corrections.json '\
+                   'only has the commits it includes')
+end
+tokens = log.split("\0").map { |token| token.delete_prefix("\n") }
+commit = date = subject = nil
+renamed = {} # older name => the name it has now
+until tokens.empty?
+  status = tokens.shift
+  next if status.empty?
+  if status.match?(/\A\h{40}\t/) # a commit, followed by the files it changed
+    commit, date, subject = status.split("\t", 3)
+    next
+  end
 
-# strip trailing whitespace from CSV files
-after_build do |builder|
-  base = File.join(config[:build_dir], 'results')
-  builder.thor.gsub_file File.join(base, 'schools.csv'), /\s+\Z/, ''
-  builder.thor.gsub_file File.join(base, 'events.csv' ), /\s+\Z/, ''
+  paths = tokens.shift(status.start_with?('R', 'C') ? 2 : 1)
+  old, new = paths.map { |path| path[%r{\Adata/([^/]+)\.yaml\z}, 1] }
+  new ||= old if paths.size == 1
+  next unless new
+
+  filename = renamed.fetch(new, new)
+  renamed[old] = filename if old && paths.size == 2
+  next if status == 'R100'
+
+  history[filename] << { commit: commit, date: date, subject: subject }
+end
+ignore_prefix = ENV['CORRECTIONS_IGNORE']
+corrections = interpreters.keys.sort.map do |filename|
+  [filename, history[filename][0...-1].reject do |c|
+    ignore_prefix && c[:subject].start_with?(ignore_prefix)
+  end]
+end.reject { |_, commits| commits.empty? }.to_h
+
+# results marked `preliminary` under `unosmium` are expected to be replaced by
+# the final results soon, so ones left unchanged for too long are warned about
+preliminary_days = (ENV['PRELIMINARY_DAYS'] || '14').to_i
+extensions.select { |_, ext| ext[:preliminary] }.each_key do |filename|
+  last = history[filename].first
+  next unless last
+
+  days = (Date.today - Date.parse(last[:date])).to_i
+  next if days <= preliminary_days
+
+  add_warning.call('stale-preliminary', "data/#{filename}.yaml",
+                   "marked preliminary but unchanged for #{days} days",
+                   days: days, commit: last[:commit])
+end
+
+# Results published as preliminary rounds and finals in two files are linked by
+# `prelims` (the preliminary file's name) under `unosmium` in the finals -- the
+# preliminary results still get their own page, but the index, by-school page,
+# and other aggregates only count the finals
+prelims = {}
+extensions.each do |filename, ext|
+  next unless ext[:prelims]
+
+  if interpreters.key?(ext[:prelims].to_s)
+    prelims[ext[:prelims].to_s] = filename
+  else
+    add_warning.call('bad-prelims', "data/#{filename}.yaml",
+                     "prelims #{ext[:prelims]} is not a results file",
+                     prelims: ext[:prelims].to_s)
+  end
+end
+interpreters.each_key do |filename|
+  next unless filename[11..-3].to_s.end_with?('prelims')
+  next if prelims.key?(filename)
+
+  add_warning.call('unlinked-prelims', "data/#{filename}.yaml",
+                   'looks like preliminary results, add `prelims: '\
+                   "#{filename}` under `unosmium` in its finals",
+                   severity: 'info')
+end
+finals = interpreters.reject { |filename, _| prelims.key?(filename) }
+
+# Seasons each event was official in each division, from
+# data/official_events.yaml -- ranges can't overlap, events and divisions that
+# no results file has (prelims included, but not files left out by
+# .resultsignore) are warned about, and so are regular (not trial) events held
+# in a season their division's ranges leave out, since either the ranges or the
+# results file are probably wrong
+official_events = YAML.safe_load(File.read(File.join(@app.root, 'data',
+                                                     'official_events.yaml')))
+official_events ||= {}
+seen_events = interpreters.values.flat_map do |i|
+  i.events.map { |e| [e.name, i.tournament.division] }
+end.uniq
+official_events.each do |name, divisions|
+  (divisions || {}).each do |division, ranges|
+    unless seen_events.include?([name, division.to_s])
+      add_warning.call('unseen-official', 'data/official_events.yaml',
+                       "#{name} is listed for division #{division}, which no "\
+                       'results file has', event: name, division: division.to_s)
+    end
+
+    spans = Array(ranges).map do |range|
+      from, to = range.values_at('from', 'to') if range.is_a?(Hash)
+      unless from.is_a?(Integer) &&
+             (to.nil? || (to.is_a?(Integer) && from <= to))
+        raise ArgumentError, "official event #{name} (#{division}) has a bad "\
+                             "range #{range.inspect}"
+      end
+
+      [from, to || Float::INFINITY]
+    end
+    spans.combination(2).each do |a, b|
+      next if a[1] < b[0] || b[1] < a[0]
+
+      raise ArgumentError, "official event #{name} (#{division}) has "\
+                           'overlapping ranges in data/official_events.yaml'
+    end
+  end
+end
+finals.each do |filename, i|
+  year = i.tournament.year
+  i.events.reject(&:trial?).each do |e|
+    ranges = (official_events[e.name] || {})[i.tournament.division]
+    next if ranges.nil? || ranges.any? { |r| (r['from']..r['to']).cover?(year) }
+
+    add_warning.call('unofficial-event', "data/#{filename}.yaml",
+                     "#{e.name} was not an official Division "\
+                     "#{i.tournament.division} event in #{year}",
+                     event: e.name, season: year)
+  end
+end
+
+# A results file can be published under a shorter name than its file name with
+# `slug` under `unosmium` (e.g. 2024-mit-c) -- its pages and exports use the
+# slug, links to it go through page_name, and its old URLs are redirected in
+# the _redirects file Netlify reads. Slugs must be lowercase letters, digits,
+# dots, dashes and underscores, and can't clash with any other page name,
+# including the fixed ones like offline or placeholder.
+#
+# Page names also have to work when the build is checked out on Windows, so
+# slugs can't end in a dot or be a reserved device name (e.g. nul), and results
+# files with names that aren't valid there (e.g. with a colon from a time) are
+# published under a slug with the offending parts replaced.
+windows_reserved = /\A(con|prn|aux|nul|com[1-9]|lpt[1-9])(\.|\z)/i
+windows_safe = lambda do |name|
+  safe = name.gsub(%r{[<>:"/\\|?*\x00-\x1f]}, '_').sub(/[. ]+\z/, '')
+  safe.match?(windows_reserved) ? "_#{safe}" : safe
+end
+# the pages that every build makes under results/ (e.g. offline, placeholder
+# or fallback), and the 404 page, which a slug mustn't take over either
+fixed_pages = Dir.children(File.join(@app.root, 'source', 'results'))
+                 .map { |file| file.split('.').first } + ['404']
+slugs = {}
+extensions.each do |filename, ext|
+  next unless ext[:slug]
+
+  slug = ext[:slug].to_s
+  taken = interpreters.keys + slugs.values + fixed_pages - [filename]
+  if !slug.match?(/\A[a-z0-9][a-z0-9._-]*\z/) || taken.include?(slug) ||
+     windows_safe.call(slug) != slug
+    add_warning.call('bad-slug', "data/#{filename}.yaml",
+                     "slug #{slug} is not a valid or unused page name, using "\
+                     'the file name', slug: slug)
+    next
+  end
+
+  slugs[filename] = slug unless slug == filename
+end
+interpreters.each_key do |filename|
+  safe = windows_safe.call(filename)
+  next if slugs.key?(filename) || safe == filename
+
+  taken = interpreters.keys + slugs.values + fixed_pages
+  if safe.empty? || taken.include?(safe)
+    raise ArgumentError, "data/#{filename}.yaml needs a slug, as its name is "\
+                         'not a valid file name on Windows'
+  end
+
+  add_warning.call('unsafe-page-name', "data/#{filename}.yaml",
+                   "publishing as #{safe}, since the file name is not valid "\
+                   'on Windows', slug: safe)
+  slugs[filename] = safe
+end
+interpreters.keys.map { |filename| slugs.fetch(filename, filename) }
+            .group_by(&:downcase).each_value do |names|
+  next if names.size < 2
+
+  add_warning.call('page-name-clash', 'data/',
+                   "pages #{names.join(', ')} would overwrite each other on "\
+                   'Windows', names: names)
+end
+set :page_slugs, slugs.freeze
+after_build do
+  next if slugs.empty?
+
+  redirects = slugs.flat_map do |filename, slug|
+    %w[html csv summary.html attendance.html attendance.csv].map do |ext|
+      "/results/#{filename}.#{ext} /results/#{slug}.#{ext} 301\n"
+    end
+  end
+  File.write(File.join(config[:build_dir], '_redirects'), redirects.join)
+end
+
+# Page view counts exported from analytics as viewcounts.csv (path, views rows,
+# e.g. /results/2019-06-01_nationals_c.html,1234), put on the index cards so
+# they can be sorted by popularity -- tournaments not listed have 0 views.
+# Paths are matched on the page names tournaments are published under, and on
+# the file names that slugged pages are redirected from
+views = Hash.new(0)
+view_pages = interpreters.keys.to_h { |f| [f, f] }
+                         .merge(slugs.to_h { |f, slug| [slug, f] })
+views_file = File.join(@app.root, 'viewcounts.csv')
+if File.exist?(views_file)
+  unmatched = []
+  CSV.foreach(views_file) do |path, count|
+    next unless count.to_s.match?(/\A\d+\z/) # e.g. a header row
+
+    name = path.to_s.strip[%r{\A/?results/([^/]+?)(\.html)?\z}, 1]
+    filename = view_pages[name]
+    if filename
+      views[filename] += count.to_i
+    else
+      unmatched << path
+    end
+  end
+  unmatched.uniq.each do |path|
+    add_warning.call('unmatched-views', 'viewcounts.csv',
+                     "no tournament page at #{path}", path: path)
+  end
+end
+
+# A tournament can have its own stylesheet (e.g. for a header image) with
+# `theme_css: true` under `unosmium`, at source/themes/<page name>.theme.css.
+# It is only linked from that tournament's page, where the body gets the
+# custom-theme class, and every rule in it has to be scoped under that class so
+# that it can't reach anything else -- it is also kept small, since it's loaded
+# on top of the usual styles.
+theme_max_bytes = 16 * 1024
+extensions.select { |_, ext| ext[:theme_css] }.each_key do |filename|
+  file = "source/themes/#{slugs.fetch(filename, filename)}.theme.css"
+  path = File.join(@app.root, file)
+  unless File.exist?(path)
+    raise ArgumentError, "data/#{filename}.yaml has theme_css, but #{file} "\
+                         'does not exist'
+  end
+  if File.size(path) > theme_max_bytes
+    raise ArgumentError, "#{file} is over #{theme_max_bytes} bytes"
+  end
+
+  css = File.read(path).gsub(%r{/\*.*?\*/}m, '')
+  unscoped = css.scan(/([^{};]+)\{/).flatten.map(&:strip)
+                .reject { |prelude| prelude.start_with?('@') }
+                .flat_map { |prelude| prelude.split(',').map(&:strip) }
+                .reject { |selector| selector.start_with?('.custom-theme') }
+  next if unscoped.empty?
+
+  raise ArgumentError, "#{file} has rules not scoped under .custom-theme: "\
+                       "#{unscoped.join(', ')}"
+end
+
+# Photos from a tournament (e.g. of the awards ceremony) can be put in
+# source/images/media/<page name>/, and are shown in a gallery at the bottom of
+# its page, with alt text from a captions.yaml in the same directory (file name:
+# caption). scripts/media.sh downscales large photos and makes the thumbnails
+# the gallery shows, under thumbs/ -- photos without one are left out.
+media_root = File.join(@app.root, 'source', 'images', 'media')
+media = Hash.new([])
+interpreters.each_key do |filename|
+  name = slugs.fetch(filename, filename)
+  dir = File.join(media_root, name)
+  next unless File.directory?(dir)
+
+  captions_file = File.join(dir, 'captions.yaml')
+  captions = {}
+  if File.exist?(captions_file)
+    captions = YAML.safe_load(File.read(captions_file)) || {}
+  end
+  images = Dir.children(dir).sort.reject do |file|
+    file == 'captions.yaml' || File.directory?(File.join(dir, file))
+  end
+  media[filename] = images.filter_map do |file|
+    path = "source/images/media/#{name}/#{file}"
+    thumb = "thumbs/#{File.basename(file, '.*')}.jpg"
+    unless file.match?(/\.(jpe?g|png|webp)\z/i)
+      add_warning.call('unsupported-media', path,
+                       'skipping file that is not a JPEG, PNG or WebP image')
+      next
+    end
+    unless File.exist?(File.join(dir, thumb))
+      add_warning.call('missing-thumbnail', path,
+                       'skipping image without a thumbnail (see '\
+                       'scripts/media.sh)')
+      next
+    end
+
+    { image: "../images/media/#{name}/#{file}",
+      thumb: "../images/media/#{name}/#{thumb}",
+      caption: captions[file].to_s }
+  end
+end
+
+# Coordinates of tournament sites, read from data/locations.csv (page name,
+# latitude, longitude columns under a header row, e.g.
+# 2019-06-01_nationals_c,40.0076,-83.0186) -- the coordinates are looked up by
+# hand rather than geocoded during the build, so tournaments not listed just go
+# without a map link on their page and aren't in /results/locations.geojson
+locations = {}
+locations_file = File.join(@app.root, 'data', 'locations.csv')
+if File.exist?(locations_file)
+  page_names = interpreters.keys.map { |f| [slugs.fetch(f, f), f] }.to_h
+  CSV.foreach(locations_file, headers: true).with_index(2) do |row, line|
+    name, latitude, longitude = row.fields.map { |field| field.to_s.strip }
+    coordinates = [latitude, longitude].map { |c| Float(c, exception: false) }
+    if coordinates.include?(nil) || coordinates[0].abs > 90 ||
+       coordinates[1].abs > 180
+      raise ArgumentError,
+            "data/locations.csv line #{line} has coordinates #{latitude}, "\
+            "#{longitude}, which are not a latitude and longitude"
+    end
+
+    if page_names.key?(name)
+      locations[page_names[name]] = coordinates
+    else
+      add_warning.call('unknown-location', 'data/locations.csv',
+                       "no tournament page named #{name}", name: name)
+    end
+  end
+end
+
+priorities = finals.keys.map do |filename|
+  [filename, index_priority.call(extensions[filename])]
+end.to_h
+
+# Each entry in data/upcoming.yaml needs a name, a link and a date (unquoted, so
+# that YAML reads it as one) -- entries without are warned about and left out
+# of the index, the calendars and the placeholder pages
+upcoming = data.upcoming.to_a.select.with_index(1) do |info, number|
+  next true if info[:name] && info[:link] && info[:date].is_a?(Date)
+
+  add_warning.call('bad-upcoming', 'data/upcoming.yaml',
+                   "skipping entry #{number}, which needs a name, link and "\
+                   'date', entry: number)
+  false
+end
+
+# every school's results per season, and each tournament's strength of field
+# worked out from them, which several pages and exports need -- going through
+# every team of every tournament for each school, so this is done only once
+school_stats = helper_methods.school_seasons(finals)
+school_stats.each do |slug, school|
+  next if slug == helper_methods.safe_slug(school[:name])
+
+  add_warning.call('school-slug-clash', 'data/',
+                   "#{school[:name]} has the same anchor as another school, "\
+                   "using #{slug}", school: school[:name], slug: slug)
+end
+strengths = helper_methods.strength_of_field(finals, school_stats)
+
+index_locals = { interpreters: finals, extensions: extensions, views: views,
+                 priorities: priorities, prelims: prelims.invert,
+                 strengths: strengths, upcoming: upcoming }
+page '/results/index.html', locals: index_locals
+page '/results/performance.html'
+page '/results/schools.csv', locals: { interpreters: finals }
+page '/results/events.csv', locals: { interpreters: finals }
+page '/results/events.html', locals: { interpreters: finals }
+page '/results/events_by_division.csv', locals: { interpreters: finals }
+page '/results/events_annotated.csv', locals: { interpreters: finals }
+page '/results/trophies.json', locals: { interpreters: finals }
+page '/results/records.html', locals: { interpreters: finals }
+page '/results/records.json', locals: { interpreters: finals }
+page '/results/schools.json', locals: { seasons: school_stats }
+page '/results/schools/summary.json', locals: { seasons: school_stats }
+page '/results/rankings.json', locals: { interpreters: finals,
+                                         strengths: strengths }
+page '/results/rankings.csv', locals: { interpreters: finals }
+page '/results/podiums.json', locals: { interpreters: finals }
+page '/404.html', locals: { interpreters: finals }
+page '/results/corrections.json', locals: { corrections: corrections }
+page '/results/attributions.html', locals: { interpreters: interpreters }
+page '/results/locations.geojson',
+     locals: { interpreters: finals, locations: locations }
+
+# team numbers at every tournament of a season, for matching up scoresheets
+interpreters.group_by { |_, i| i.tournament.year }.each do |year, season|
+  %w[csv json].each do |ext|
+    proxy "/results/team-numbers/#{year}.#{ext}",
+          "/results/team-numbers.#{ext}",
+          locals: { interpreters: season.to_h }
+  end
+end
+
+# Tags listed under `tags` in a results file's unosmium block must be one of the
+# tags in data/tags.yaml, and each tag used gets a page listing its tournaments
+allowed_tags = YAML.safe_load(File.read(File.join(@app.root, 'data',
+                                                  'tags.yaml'))) || {}
+tagged = Hash.new { |h, tag| h[tag] = {} }
+finals.each_key do |filename|
+  Array(extensions[filename][:tags]).map(&:to_s).each do |tag|
+    unless allowed_tags.key?(tag)
+      raise ArgumentError,
+            "data/#{filename}.yaml has tag #{tag}, which is not one of "\
+            "#{allowed_tags.keys.join(', ')} (see data/tags.yaml)"
+    end
+
+    tagged[tag][filename] = finals[filename]
+  end
+end
+tagged.each do |tag, tournaments|
+  proxy "/results/tags/#{tag}.html", '/results/tag.html',
+        locals: { tag: tag, description: allowed_tags[tag],
+                  interpreters: tournaments }
+end
+
+# all-time leaderboards of schools for each division
+finals.values.map { |i| i.tournament.division }.uniq.sort.each do |division|
+  %w[html csv].each do |ext|
+    proxy "/results/all-time-#{division.downcase}.#{ext}",
+          "/results/all-time.#{ext}",
+          locals: { interpreters: finals, division: division }
+  end
+end
+
+# a calendar of each season's tournaments per division, including upcoming ones
+# listed with a results file name that haven't been posted yet (and haven't
+# been cancelled)
+pending = upcoming.select do |info|
+  info.key?(:file) && !interpreters.key?(info[:file]) && !info[:cancelled]
+end
+finals.group_by { |_, i| [i.tournament.year, i.tournament.division] }
+      .each do |(year, division), season|
+  proxy "/results/calendar/#{year}-#{division.downcase}.html",
+        '/results/calendar.html',
+        locals: { year: year, division: division, tournaments: season.to_h,
+                  pending: pending.select do |info|
+                    season_of.call(info[:date]) == year &&
+                      info[:file].end_with?("_#{division.downcase}")
+                  end }
+end
+
+# Calendar feeds of the tournaments a school usually goes to, for planning a
+# season: schools at FEED_MIN or more tournaments (3 by default) get
+# /results/schools/<school>.ics with this season's tournaments, posted or
+# upcoming, from every series the school went to in the FEED_LOOKBACK seasons
+# before (2 by default). A series is the file name without its date, e.g.
+# medford_invitational_c, the same way logos are matched.
+series_of = ->(filename) { filename.to_s[11..] }
+# (with nothing posted or upcoming, this season is the one today falls in)
+season_now = [*finals.values.map { |i| i.tournament.year },
+              *pending.map { |info| season_of.call(info[:date]) }].max ||
+             season_of.call(Date.today)
+lookback = ((season_now - (ENV['FEED_LOOKBACK'] || 2).to_i)...season_now)
+this_season = finals.select { |_, i| i.tournament.year == season_now }
+                    .map do |filename, i|
+  title = helper_methods.tournament_title(i.tournament)
+  { series: series_of.call(filename), page: slugs.fetch(filename, filename),
+    name: "#{i.tournament.year} #{title}", date: i.tournament.date,
+    location: i.tournament.location }
+end
+pending.select { |info| season_of.call(info[:date]) == season_now }
+       .each do |info|
+  this_season << { series: series_of.call(info[:file]), page: info[:file],
+                   name: info[:name], date: info[:date] }
+end
+attended = Hash.new { |h, school| h[school] = {} }
+finals.each do |filename, i|
+  i.teams.reject(&:exhibition?).each do |team|
+    school = helper_methods.full_school_name(team)
+    attended[school][filename] = i.tournament.year
+  end
+end
+feeds = {}
+attended.each do |school, tournaments|
+  next if tournaments.size < (ENV['FEED_MIN'] || 3).to_i
+
+  series = tournaments.select { |_, year| lookback.cover?(year) }
+                      .keys.map(&series_of)
+  planned = this_season.select { |t| series.include?(t[:series]) }
+  next if planned.empty?
+
+  feeds[school] = helper_methods.school_slug(school)
+  proxy "/results/schools/#{feeds[school]}.ics", '/results/schools/feed.ics',
+        locals: { school: school,
+                  tournaments: planned.sort_by { |t| [t[:date], t[:name]] } }
+end
+schools_locals = { interpreters: finals, feeds: feeds, seasons: school_stats }
+page '/results/schools.html', locals: schools_locals
+
+# Leaderboards of the schools with the highest performance index for partner
+# sites to embed, as /results/widgets/leaderboard-<season>-<division>.html with
+# the top 10 and a .compact.html with the top 5 -- standalone pages with inline
+# styles and no scripts, so they work in an iframe or a server-side include
+finals.values.map { |i| [i.tournament.year, i.tournament.division] }.uniq
+      .each do |year, division|
+  { '' => 10, '.compact' => 5 }.each do |variant, top|
+    schools = helper_methods.leaderboard(school_stats, year, division, top: top)
+    next if schools.empty?
+
+    proxy "/results/widgets/leaderboard-#{year}-#{division.downcase}"\
+          "#{variant}.html",
+          '/results/widgets/leaderboard.html',
+          locals: { year: year, division: division, schools: schools }
+  end
+end
+
+if ENV['OFFLINE_ARCHIVE']
+  page '/results/archive.html', locals: { interpreters: interpreters }
+end
+if ENV['LOGO_REPORT']
+  page '/results/logo-report.html', locals: { interpreters: interpreters }
+end
+
+# Translated copies of the index and by-school pages, e.g. under /es/results/,
+# for each locale in LOCALES (comma-separated) with strings in data/locales --
+# the results pages themselves are only in English
+ENV['LOCALES'].to_s.split(',').each do |locale|
+  proxy "/#{locale}/results/index.html", '/results/index.html',
+        locals: index_locals
+  proxy "/#{locale}/results/schools.html", '/results/schools.html',
+        locals: schools_locals
+end
+
+# what the site was built from, and how much of it, for telling which commit a
+# deploy is from -- along with each tournament's strength of field, by page name
+after_build do
+  info = CustomHelpers::BUILD_INFO.merge(
+    tournaments: interpreters.count,
+    pages: Dir[File.join(config[:build_dir], '**', '*.html')].count,
+    strength_of_field: strengths.transform_keys { |f| slugs.fetch(f, f) }
+  )
+  File.write(File.join(config[:build_dir], 'results', 'build-info.json'),
+             JSON.pretty_generate(info))
+end
+
+# With METRICS_CSV set to a file kept between builds (e.g. metrics.csv), each
+# build adds a row to it of how big the archive is and how long the build took,
+# replacing any row from the same day so that re-running a build doesn't count
+# twice -- the file is also copied to results/metrics.csv, and the new row
+# written as results/metrics.json
+if ENV['METRICS_CSV']
+  after_build do
+    results_dir = File.join(config[:build_dir], 'results')
+    files = Dir[File.join(config[:build_dir], '**', '*')].select do |path|
+      File.file?(path)
+    end
+    teams = interpreters.values.flat_map(&:teams)
+    schools = teams.map { |t| helper_methods.full_school_name(t) }.uniq
+    events = interpreters.values.flat_map(&:events).map(&:name).uniq
+    elapsed = Process.clock_gettime(Process::CLOCK_MONOTONIC) - build_started
+    metrics = {
+      'date' => Date.today.iso8601,
+      'tournaments' => interpreters.count,
+      'schools' => schools.count,
+      'teams' => teams.count,
+      'events' => events.count,
+      'output_bytes' => files.sum { |path| File.size(path) },
+      'build_seconds' => elapsed.round(1)
+    }
+    rows = []
+    if File.exist?(ENV['METRICS_CSV'])
+      rows = CSV.read(ENV['METRICS_CSV'], headers: true).map(&:to_h)
+    end
+    rows.reject! { |row| row['date'] == metrics['date'] }
+    rows << metrics
+    csv = CSV.generate do |out|
+      out << metrics.keys
+      rows.sort_by { |row| row['date'] }
+          .each { |row| out << row.values_at(*metrics.keys) }
+    end
+    File.write(ENV['METRICS_CSV'], csv)
+    File.write(File.join(results_dir, 'metrics.csv'), csv)
+    File.write(File.join(results_dir, 'metrics.json'),
+               JSON.pretty_generate(metrics))
+  end
+end
+
+# the JSON exports have to match the schemas published with them, see
+# scripts/validate-exports.rb
+schemas_dir = File.join(@app.root, 'source', 'results')
+after_build do
+  problems = ExportSchemas.check(config[:build_dir], schemas_dir)
+  raise "Export checks failed:\n#{problems.join("\n")}" unless problems.empty?
+end
+
+# Checks on every generated page, which fail the build if one doesn't hold
+# (e.g. after a template typo) -- each is a description of what a page should
+# do and a test on its HTML, with extra checks for the pages whose names match
+# a pattern (or are in a list) in page_checks -- the results table check is only
+# for pages made from the results files SciolyFF read, not the placeholder or
+# fallback pages that share their names' shape
+results_pages = interpreters.keys.to_set { |f| "#{slugs.fetch(f, f)}.html" }
+common_checks = {
+  'have a non-empty <title>' =>
+    ->(html) { html.match?(%r{<title>[^<]*\S[^<]*</title>}) },
+  'have no leftover ERB tags' => ->(html) { !html.include?('<%') },
+  'have a src on every <img>' => lambda do |html|
+    html.scan(/<img\b[^>]*>/).all? { |img| img.match?(/\bsrc="[^"]+"/) }
+  end
+}
+page_checks = {
+  results_pages => {
+    'have exactly one results table' =>
+      ->(html) { html.scan(/<table class="results-classic\b/).count == 1 }
+  },
+  /\Aleaderboard-/ => {
+    'stay under 10 KB' => ->(html) { html.bytesize < 10 * 1024 }
+  }
+}
+after_build do
+  pages = Dir[File.join(config[:build_dir], '**', '*.html')].sort
+  problems = pages.flat_map do |path|
+    name = File.basename(path)
+    html = File.read(path)
+    checks = page_checks.select { |pattern, _| pattern === name }
+                        .values.reduce(common_checks, :merge)
+    checks.reject { |_, check| check.call(html) }
+          .map { |description, _| "#{path}: does not #{description}" }
+  end
+  raise "Page checks failed:\n#{problems.join("\n")}" unless problems.empty?
+end
+
+# with STRICT_A11Y set, fail the build if any generated page doesn't have
+# exactly one <h1> or has a table without a <thead> of header cells
+if ENV['STRICT_A11Y']
+  after_build do
+    pages = Dir[File.join(config[:build_dir], 'results', '*.html')]
+    problems = pages.sort.flat_map do |path|
+      html = File.read(path)
+      name = File.basename(path)
+      h1_count = html.scan(/<h1[\s>]/).count
+      [
+        ("#{name}: has #{h1_count} <h1> elements" unless h1_count == 1),
+        *html.scan(%r{<table.*?</table>}m)
+             .reject { |table| table =~ %r{<thead.*<th[\s>].*</thead>}m }
+             .map { "#{name}: has a <table> without header cells" }
+      ].compact
+    end
+    unless problems.empty?
+      raise "Accessibility check failed:\n#{problems.join("\n")}"
+    end
+  end
+end
+
+# With SIGNING_KEY set to an Ed25519 private key (PEM, kept outside the build
+# directory, and needing openssl gem 3.0+ as checked above), the CSV and JSON
+# exports are listed with their SHA-256 hashes in results/SHA256SUMS, and it and
+# every export get a detached .sig signature -- scripts/verify-signatures.sh
+# checks them against the public key
+if ENV['SIGNING_KEY']
+  signing_key = OpenSSL::PKey.read(File.read(ENV['SIGNING_KEY']))
+  unless signing_key.oid == 'ED25519'
+    raise ArgumentError,
+          "SIGNING_KEY #{ENV['SIGNING_KEY']} is not an Ed25519 key"
+  end
+
+  after_build do
+    results_dir = File.join(config[:build_dir], 'results')
+    exports = Dir[File.join(results_dir, '**', '*.{csv,json,geojson,tsv}')].sort
+    sums = exports.map do |path|
+      relative = path.delete_prefix("#{results_dir}/")
+      "#{Digest::SHA256.file(path).hexdigest}  #{relative}\n"
+    end.join
+    File.write(File.join(results_dir, 'SHA256SUMS'), sums)
+    [*exports, File.join(results_dir, 'SHA256SUMS')].each do |path|
+      File.binwrite("#{path}.sig", signing_key.sign(nil, File.binread(path)))
+    end
+  end
 end
 
 return if ENV['INDEX_ONLY']
 
+# Tournaments with at least SUMMARY_MIN_TEAMS teams (60 by default, so usually
+# only nationals) also get a light <name>.summary.html without any scripts, for
+# checking the top teams and event winners on a phone at awards
+summary_min_teams = (ENV['SUMMARY_MIN_TEAMS'] || 60).to_i
+
 interpreters.each do |filename, interpreter|
-  proxy "/results/#{filename}.html",
+  summary = interpreter.teams.count >= summary_min_teams
+  name = slugs.fetch(filename, filename)
+  proxy "/results/#{name}.html",
         '/results/template.html',
+        locals: { i: interpreter, ext: extensions[filename],
+                  summary: summary, filename: filename,
+                  location: locations[filename], media: media[filename] }
+  if summary
+    proxy "/results/#{name}.summary.html",
+          '/results/summary.html',
+          locals: { i: interpreter, filename: filename }
+  end
+  proxy "/results/#{name}.csv",
+        '/results/template.csv',
         locals: { i: interpreter }
+  proxy "/results/#{name}.attendance.html",
+        '/results/attendance.html',
+        locals: { i: interpreter, filename: filename }
+  proxy "/results/#{name}.attendance.csv",
+        '/results/attendance.csv',
+        locals: { i: interpreter }
+end
+
+# the build still fails after writing the fallback pages, unless
+# ALLOW_RENDER_FALLBACK is set (e.g. to publish the rest of the results while
+# a file SciolyFF can't handle is looked into)
+fallbacks.each do |filename, tournament|
+  proxy "/results/#{filename}.html",
+        '/results/fallback.html',
+        locals: { t: tournament, filename: filename, empty: false }
+end
+empties.each do |filename, tournament|
+  proxy "/results/#{filename}.html",
+        '/results/fallback.html',
+        locals: { t: tournament, filename: filename, empty: true }
 end
+after_build do
+  next if fallbacks.empty? || ENV['ALLOW_RENDER_FALLBACK']
 
-data.upcoming.each do |info|
-  next unless info.key?(:file) && !interpreters.key?(info[:file])
+  raise "SciolyFF failed on #{fallbacks.keys.join(', ')}, see the warnings "\
+        'above (set ALLOW_RENDER_FALLBACK to allow fallback pages)'
+end
+
+upcoming.each do |info|
+  next unless info.key?(:file) && !interpreters.key?(info[:file]) &&
+              !fallbacks.key?(info[:file]) && !empties.key?(info[:file])
 
   proxy "/results/#{info[:file]}.html",
         '/results/placeholder.html',
@@ -69,12 +1335,15 @@ end
 # Activate and configure extensions
 # https://middlemanapp.com/advanced/configuration/#configuring-extensions
 
-activate :external_pipeline,
-         name: :webpack,
-         command: if build?
-                    'yarn run webpack --bail'
-                  else
-                    'yarn run webpack --watch -d'
-                  end,
-         source: '.tmp/dist',
-         latency: 1
+# the webpack assets are only needed by the pages
+unless ENV['EXPORTS_ONLY']
+  activate :external_pipeline,
+           name: :webpack,
+           command: if build?
+                      'yarn run webpack --bail'
+                    else
+                      'yarn run webpack --watch -d'
+                    end,
+           source: '.tmp/dist',
+           latency: 1
+end
@@ -1,6 +1,13 @@
 # frozen_string_literal: true
 
+require 'digest'
+require 'fastimage'
+require 'kramdown'
 require 'miro'
+require 'set'
+require 'tempfile'
+require 'time'
+require 'uri'
 
 # Methods used in templates
 # https://middlemanapp.com/basics/helper-methods/
@@ -60,32 +67,289 @@ module CustomHelpers
     WY: 'Wyoming'
   }.freeze
 
+  MISSING_TRANSLATIONS ||= Set.new
+
+  NOTES_ALLOWED_TAGS ||= %w[p br em strong code ul ol li a].freeze
+
+  # bounds darkening in legible_bg_color when a contrast target can't be met
+  MAX_DARKEN_STEPS ||= 20
+
+  # most teams drawn in a placement heatmap, which would otherwise get huge
+  HEATMAP_MAX_ROWS ||= 80
+
   IMAGES_PATH ||= Pathname.new(__dir__) + '..' + 'source' + 'images'
 
+  # what the site was built from, for the page footers and build-info.json --
+  # the build time is left out with REPRODUCIBLE_BUILD set, so that building the
+  # same commit twice gives identical output
+  BUILD_INFO ||= begin
+    git = lambda do |*args|
+      out = IO.popen(['git', '-C', __dir__, *args], err: File::NULL, &:read)
+      $?.success? ? out.strip : 'unknown'
+    rescue SystemCallError
+      'unknown'
+    end
+    {
+      commit: git.call('rev-parse', 'HEAD'),
+      commit_date: git.call('log', '-1', '--format=%cI'),
+      sciolyff: Gem.loaded_specs['sciolyff']&.version&.to_s || 'unknown',
+      generated_at: (Time.now.utc.iso8601 unless ENV['REPRODUCIBLE_BUILD'])
+    }.compact.freeze
+  end
+
+  # logos picked by find_logo_path during the build, and the tournaments left
+  # with the default logo, for the logo report in config.rb
+  USED_LOGOS ||= Set.new
+  DEFAULT_LOGO_TOURNAMENTS ||= Set.new
+
+  # tournaments whose theme color had to be darkened further than THEME_DRIFT
+  # (as a CIE76 distance) from their logo color, also for config.rb to report
+  DRIFTED_THEME_COLORS ||= {}
+
+  # anchors of the schools on the by-school page (which also key
+  # school_seasons) by school name -- set by school_seasons, so that schools
+  # whose names come out the same from safe_slug are told apart the same way on
+  # every page
+  SCHOOL_SLUGS ||= {}
+
+  # the logo files, the logo picked for each tournament name, division suffix
+  # and year (which is all the choice depends on), and theme colors by results
+  # file name -- most tournaments are looked up by several pages, and taking a
+  # color from a logo means reading the whole image, so these are worked out
+  # once, and again only when a logo is added, removed or renamed (which
+  # changes the directory's mtime), e.g. while `middleman server` is running
+  LOGO_CACHE ||= {}
+
+  def logo_cache
+    stamp = File.mtime(IMAGES_PATH + 'logos')
+    return LOGO_CACHE if LOGO_CACHE[:stamp] == stamp
+
+    LOGO_CACHE.replace(
+      stamp: stamp,
+      files: Dir.children(IMAGES_PATH + 'logos')
+                .select(&:valid_encoding?) # warned about by config.rb
+                .freeze,
+      choices: {}, colors: {}
+    )
+  end
+
   # gets the newest matching logo with year less than tournament year
   def find_logo_path(filename)
     tournament_year = filename[0...4].to_i
     tournament_name = filename[11..-3]
     get_year = ->(image) { image[/^[0-9]+/].to_i }
 
-    Pathname.new('../images/logos/') +
-    Dir.children(IMAGES_PATH + 'logos')
-       .select { |image| image.include? tournament_name }
-       .select { |i| filename.end_with? i.split('.').first[/_[abc]$/].to_s }
-       .append('default.jpg')
-       .select { |image| get_year.call(image) <= tournament_year }
-       .max_by { |image| get_year.call(image) + image.length / 100.0 }
-       .dup # string may be frozen
+    key = [tournament_name, filename[-2..], tournament_year]
+    cache = logo_cache
+    logos = cache[:files]
+    logo = cache[:choices][key] ||=
+      logos.select { |image| image.include? tournament_name }
+           .select do |image|
+             filename.end_with? image.split('.').first[/_[abc]$/].to_s
+           end
+           .append('default.jpg')
+           .select { |image| get_year.call(image) <= tournament_year }
+           .max_by { |image| get_year.call(image) + image.length / 100.0 }
+    if logo == 'default.jpg'
+      DEFAULT_LOGO_TOURNAMENTS << filename
+    else
+      USED_LOGOS << logo
+    end
+    Pathname.new('../images/logos/') + logo.dup # string may be frozen
+  end
+
+  # pixel width and height of a tournament's logo, or nil if unreadable
+  def logo_dimensions(filename)
+    FastImage.size((IMAGES_PATH + find_logo_path(filename)).to_s)
   end
 
   def find_bg_color(filename)
-    colors = Miro::DominantColors
-              .new((IMAGES_PATH + find_logo_path(filename)).to_s)
-              .to_hex
-    # String#paint from the chroma gem
-    color = colors[3] ? colors[3].paint : colors.first.paint
-    color = color.darken while color.light?
-    color
+    theme_color(filename)[:color]
+  end
+
+  # how a tournament's theme color was picked, for both find_bg_color and the
+  # logo report: the logo, the color taken from it (or from the default theme
+  # colors), and the color after darkening, with the number of darkening steps
+  def theme_color(filename)
+    logo_cache[:colors][filename] ||= pick_theme_color(filename)
+  end
+
+  def pick_theme_color(filename)
+    logo_path = find_logo_path(filename)
+    if logo_path.basename.to_s == 'default.jpg'
+      color = default_theme_color(filename)
+    end
+    unless color
+      colors = still_logo((IMAGES_PATH + logo_path).to_s) do |path|
+        Miro::DominantColors.new(path).to_hex
+      end
+      # String#paint from the chroma gem
+      color = colors[3] ? colors[3].paint : colors.first.paint
+    end
+    legible, steps = legible_bg_color(color)
+    drift = cie76_distance(color, legible)
+    if drift > (ENV['THEME_DRIFT'] || '25').to_f
+      DRIFTED_THEME_COLORS[filename] = { source: color.to_hex,
+                                         color: legible.to_hex,
+                                         drift: drift.round(1), steps: steps }
+    end
+    { logo: logo_path, source: color, color: legible, steps: steps,
+      drift: drift }
+  end
+
+  # the width of every frame of a logo (or every size in an .ico), from
+  # ImageMagick -- only formats that can hold more than one are asked about, and
+  # the rest are taken to be a single frame
+  def logo_frames(path)
+    formats = %w[.gif .ico .png .webp]
+    return [] unless formats.include?(File.extname(path).downcase)
+
+    IO.popen(['identify', '-format', '%w\n', path], &:read).split.map(&:to_i)
+  end
+
+  # Miro would count the colors of every frame of an animated logo (or every
+  # size in an .ico) together, so it's given only the first frame, or the
+  # largest size of an .ico, converted with ImageMagick
+  def still_logo(path)
+    ico = path.end_with?('.ico')
+    widths = logo_frames(path)
+    return yield path unless ico || widths.size > 1
+
+    frame = ico ? widths.each_with_index.max[1] : 0
+    Tempfile.create(['logo', '.png']) do |still|
+      system('convert', "#{path}[#{frame}]", still.path, exception: true)
+      yield still.path
+    end
+  end
+
+  # darken a theme color until white text on it is legible -- by default until
+  # Chroma no longer considers it light, or with `contrast` set in
+  # data/theme_colors.yaml until white text reaches the target contrast --
+  # returning the color and how many times it was darkened
+  def legible_bg_color(color)
+    contrast = data.theme_colors['contrast']
+    steps = 0
+    unless contrast
+      while color.light?
+        color = color.darken
+        steps += 1
+      end
+      return [color, steps]
+    end
+
+    algorithm = contrast['algorithm'] || 'wcag2'
+    unless %w[wcag2 apca].include?(algorithm)
+      raise ArgumentError, "unknown contrast algorithm #{algorithm}"
+    end
+
+    MAX_DARKEN_STEPS.times do
+      break if send("#{algorithm}_contrast", color) >= contrast['target']
+
+      color = color.darken
+      steps += 1
+    end
+    [color, steps]
+  end
+
+  # CIE76 color difference, the distance between two colors in CIELAB (D65)
+  def cie76_distance(color, other)
+    lab = lambda do |c|
+      linear = %i[r g b].map do |channel|
+        v = c.rgb.send(channel) / 255.0
+        v <= 0.04045 ? v / 12.92 : ((v + 0.055) / 1.055)**2.4
+      end
+      xyz = [[0.4124, 0.3576, 0.1805], [0.2126, 0.7152, 0.0722],
+             [0.0193, 0.1192, 0.9505]].map do |row|
+        row.zip(linear).sum { |w, v| w * v }
+      end
+      f = xyz.zip([0.95047, 1.0, 1.08883]).map do |v, white|
+        t = v / white
+        t > (6.0 / 29)**3 ? t**(1.0 / 3) : t / (3 * (6.0 / 29)**2) + 4.0 / 29
+      end
+      [116 * f[1] - 16, 500 * (f[0] - f[1]), 200 * (f[1] - f[2])]
+    end
+    Math.sqrt(lab.call(color).zip(lab.call(other)).sum { |a, b| (a - b)**2 })
+  end
+
+  # WCAG 2 contrast ratio of white text on color
+  def wcag2_contrast(color)
+    linear = %i[r g b].map do |c|
+      v = color.rgb.send(c) / 255.0
+      v <= 0.03928 ? v / 12.92 : ((v + 0.055) / 1.055)**2.4
+    end
+    luminance = [0.2126, 0.7152, 0.0722].zip(linear).sum { |w, v| w * v }
+    1.05 / (luminance + 0.05)
+  end
+
+  # APCA (0.0.98G) lightness contrast Lc of white text on color, as a positive
+  # number
+  def apca_contrast(color)
+    y = [0.2126729, 0.7151522, 0.0721750].zip(%i[r g b]).sum do |w, c|
+      w * (color.rgb.send(c) / 255.0)**2.4
+    end
+    y += (0.022 - y)**1.414 if y < 0.022
+    sapc = (y**0.65 - 1.0) * 1.14
+    sapc > -0.1 ? 0.0 : -(sapc + 0.027) * 100
+  end
+
+  # color from data/theme_colors.yaml for tournaments using the default logo,
+  # with state-specific entries taking precedence over division-wide ones, and
+  # otherwise a color hashed from the tournament name if `hashed` is set
+  def default_theme_color(filename)
+    division = filename[-1].upcase
+    state = filename.split('_')[1]
+    states = data.theme_colors['states'] || {}
+    divisions = data.theme_colors['divisions'] || {}
+    rule, hex = [
+      ["states.#{state}.#{division}", (states[state] || {})[division]],
+      ["divisions.#{division}",       divisions[division]]
+    ].find { |_, c| c }
+    return hashed_theme_color(filename) if !hex && data.theme_colors['hashed']
+    return unless hex
+
+    logger.debug "#{filename}: default logo, theme color #{hex} from #{rule}"
+    hex.paint
+  end
+
+  # same hue for a tournament (name and division, not date) on every build
+  def hashed_theme_color(filename)
+    hue = Digest::MD5.hexdigest(filename[11..-1]).to_i(16) % 360
+    logger.debug "#{filename}: default logo, theme color hashed to hue #{hue}"
+    "hsl(#{hue}, 60%, 45%)".paint
+  end
+
+  # locale of the current page, English unless it was built under a locale
+  # prefix like es/ because of LOCALES in config.rb
+  def page_locale
+    current_page.path[%r{\A([a-z]{2})/}, 1] || 'en'
+  end
+
+  # other locales the current page is also built in, with their page paths
+  def page_translations
+    path = current_page.path.delete_prefix("#{page_locale}/")
+    ENV['LOCALES'].to_s.split(',').map { |l| [l, "#{l}/#{path}"] }
+                  .to_h.merge('en' => path)
+  end
+
+  # string from data/locales for our own page chrome, falling back to English
+  # (with a warning once per missing key) when the page locale doesn't have it
+  def t(key, **values)
+    string = (data.locales[page_locale] || {})[key.to_s]
+    if string.nil?
+      if MISSING_TRANSLATIONS.add?([page_locale, key.to_s])
+        logger.warn "No #{page_locale} translation for #{key}, using English"
+      end
+      string = data.locales.en.fetch(key.to_s)
+    end
+    string.is_a?(String) ? format(string, values) : string
+  end
+
+  def localized_date(date)
+    format(t(:date_format),
+           day: t(:day_names)[date.wday],
+           month: t(:month_names)[date.month - 1],
+           mday: date.day,
+           year: date.year)
   end
 
   def trophy_and_medal_colors
@@ -187,9 +451,21 @@ module CustomHelpers
     [team.school, team.suffix, location].join(' ')
   end
 
-  def search_string(interpreter)
+  # small, medium, or large by number of non-exhibition teams (across all
+  # subdivisions), with small below the first of SIZE_BUCKETS and large above
+  # the second
+  def size_bucket(interpreter)
+    small, large = (ENV['SIZE_BUCKETS'] || '20,45').split(',').map(&:to_i)
+    count = interpreter.tournament.nonexhibition_teams_count
+    if count < small then 'small'
+    elsif count <= large then 'medium'
+    else 'large'
+    end
+  end
+
+  def search_string(interpreter, tags = [])
     t = interpreter.tournament
-    words = [
+    words = Array(tags).map { |tag| "tag-#{tag}" } + [
       'science',
       'olympiad',
       'tournament',
@@ -206,6 +482,8 @@ module CustomHelpers
       t.state ? expand_state_name(t.state) : nil,
       "div-#{t.division}",
       "division-#{t.division}",
+      "level-#{t.level}",
+      "size-#{size_bucket(interpreter)}",
       t.year,
       t.date,
       t.date.strftime('%A'),
@@ -223,21 +501,58 @@ module CustomHelpers
       .any?
   end
 
+  # whether the top three would have been different without team penalties,
+  # given that a team's points already include any penalties it received
+  def penalties_affected_podium?(interpreter)
+    teams = interpreter.teams.reject(&:disqualified?)
+    without_penalties =
+      teams.each_with_index
+           .sort_by { |t, rank| [t.points - t.penalties.sum(&:points), rank] }
+           .map(&:first)
+    teams.first(3) != without_penalties.first(3)
+  end
+
+  # error pages set absolute_links, since they are served in place of pages at
+  # any depth
   def rel_link_prefix(current_page_path)
+    return '/' if current_page.data.absolute_links
     return './' unless current_page_path.include? '/'
 
     current_page_path.count('/').times.map { |_| '../' }.join
   end
 
+  # name a tournament's pages are published under, which is its results file
+  # name unless it has a `slug` (see config.rb)
+  def page_name(filename)
+    (config[:page_slugs] || {}).fetch(filename.to_s, filename.to_s)
+  end
+
+  # link to a page under /results/ from the current page, which might be a
+  # translated page under e.g. /es/results/
+  def results_link(page)
+    "#{rel_link_prefix(current_page.path)}results/#{page}"
+  end
+
+  # find_logo_path is relative to /results/, this is relative to the current
+  # page instead
+  def logo_src(filename)
+    rel_link_prefix(current_page.path) +
+      find_logo_path(filename).relative_path_from(Pathname.new('..')).to_s
+  end
+
+  # credit for a tournament's logo from data/logo_attributions.yaml, if any
+  def logo_attribution(filename)
+    data.logo_attributions[find_logo_path(filename).basename.to_s]
+  end
+
+  # e.g. 2018–19 for the season ending in 2019, which is what SciolyFF calls
+  # the tournament year
+  def season_label(year)
+    "#{year - 1}–#{format('%02d', year % 100)}"
+  end
+
   def summary_titles
-    %w[
-      Champion
-      Runner-up
-      Third-place
-      Fourth-place
-      Fifth-place
-      Sixth-place
-    ]
+    t(:summary_titles)
   end
 
   def sup_tag(placing)
@@ -264,6 +579,40 @@ module CustomHelpers
     "Qualified #{qualifiee} for the #{tournament.year} #{next_tournament}"
   end
 
+  # version of a name safe to use in URLs and HTML attributes, e.g. as an
+  # anchor on the by-school page -- whitespace becomes underscores as it always
+  # has (so existing links keep working), characters with special meaning in
+  # URLs or paths are dropped, and letters in any script are kept as is -- a
+  # name with nothing left (e.g. only punctuation) gets part of its hash instead
+  def safe_slug(name, max_length: 100)
+    slug = name.unicode_normalize(:nfc)
+               .gsub(/[[:space:]]+/, '_')
+               .gsub(/[^\p{L}\p{M}\p{N}_.,'()-]/, '')
+               .gsub(/\.{2,}/, '.')
+               .gsub(/_{2,}/, '_')
+               .gsub(/\A[_.]+|_+\z/, '')[0, max_length]
+    slug.empty? ? Digest::SHA256.hexdigest(name)[0, 12] : slug
+  end
+
+  # safe_slug of a school name, as told apart from other schools by
+  # school_slugs during the build
+  def school_slug(name)
+    SCHOOL_SLUGS.fetch(name) { safe_slug(name) }
+  end
+
+  # {name => slug} for a set of school names -- when several come out the same,
+  # the first in sorted order keeps the slug and the rest get part of their
+  # name's hash added, so a school's anchor only changes if a clash appears
+  def school_slugs(names)
+    taken = Set.new
+    names.sort.to_h do |name|
+      slug = safe_slug(name)
+      slug += "_#{Digest::SHA256.hexdigest(name)[0, 6]}" if taken.include?(slug)
+      taken << slug
+      [name, slug]
+    end
+  end
+
   def group_by_schools(interpreters)
     interpreters
       .values
@@ -283,19 +632,391 @@ module CustomHelpers
     end.to_h
   end
 
+  # counts of first, second, and third place event finishes for each school, per
+  # event across all tournaments and per season, with ties counting as the tied
+  # place for every team involved -- trial events only count if
+  # INCLUDE_TRIAL_MEDALS is set (counts are [first, second, third] arrays)
+  def trophy_case(interpreters)
+    new_counts = -> { Hash.new { |h, k| h[k] = [0, 0, 0] } }
+    cases = Hash.new do |h, k|
+      h[k] = { events: new_counts.call, seasons: new_counts.call,
+               divisions: Hash.new { |d, div| d[div] = new_counts.call } }
+    end
+    interpreters.each_value do |i|
+      i.events.each do |e|
+        next if (e.trial? || e.trialed?) && !ENV['INCLUDE_TRIAL_MEDALS']
+
+        e.placings.each do |p|
+          next if p.team.exhibition? || p.place.nil? || p.place > 3
+
+          trophies = cases[full_school_name(p.team)]
+          trophies[:events][e.name][p.place - 1] += 1
+          trophies[:seasons][i.tournament.year][p.place - 1] += 1
+          trophies[:divisions][i.tournament.division][i.tournament.year][
+            p.place - 1] += 1
+        end
+      end
+    end
+    cases.sort_by { |s, _| s.downcase.tr('^A-Za-z0-9', '') }.to_h
+  end
+
+  # per-season results for each school, keyed by school_slug of the school name,
+  # in the shape described by source/results/schools/schema.json -- ranks at
+  # each tournament are kept so that two schools can be compared head-to-head
+  def school_seasons(interpreters)
+    medals = trophy_case(interpreters)
+    schools = group_by_schools(interpreters).keys
+    SCHOOL_SLUGS.replace(school_slugs(schools))
+    schools.map do |school|
+      seasons = Hash.new { |h, k| h[k] = { tournaments: [] } }
+      interpreters.each do |filename, i|
+        ranks = i.teams
+                 .select { |t| !t.exhibition? && full_school_name(t) == school }
+                 .map(&:rank).sort
+        next if ranks.empty?
+
+        teams_count = i.tournament.nonexhibition_teams_count
+        seasons[i.tournament.year][:tournaments] << {
+          file: filename, division: i.tournament.division, ranks: ranks,
+          score: finish_score(ranks.first, teams_count)
+        }
+      end
+      seasons.each do |year, season|
+        ranks = season[:tournaments].flat_map { |t| t[:ranks] }
+        season[:average_rank] = (ranks.sum.to_f / ranks.size).round(2)
+        season[:medals] = medals.dig(school, :seasons)&.fetch(year, nil) ||
+                          [0, 0, 0]
+        season[:performance_index] = performance_index(season[:tournaments])
+        # the same split by division, for schools that moved between (or
+        # competed in more than one) -- each tournament is in one division
+        by_division = medals.dig(school, :divisions) || {}
+        season[:divisions] = season[:tournaments].group_by { |t| t[:division] }
+                                                 .sort.to_h do |division, ts|
+          [division,
+           { performance_index: performance_index(ts),
+             medals: by_division.fetch(division, {}).fetch(year, [0, 0, 0]) }]
+        end
+      end
+      [school_slug(school), { name: school, seasons: seasons.sort.to_h }]
+    end.to_h
+  end
+
+  # how well a school's best team finished at a tournament, accounting for its
+  # size -- from 1 for first to 0 for last (percentile), or as the number of
+  # standard deviations above the middle rank (zscore)
+  def finish_score(rank, teams_count)
+    if performance_settings[:method] == 'zscore'
+      sd = Math.sqrt((teams_count**2 - 1) / 12.0)
+      sd.zero? ? 0.0 : (((teams_count + 1) / 2.0 - rank) / sd).round(3)
+    else
+      teams_count > 1 ? (1 - (rank - 1).fdiv(teams_count - 1)).round(3) : 1.0
+    end
+  end
+
+  # average finish score over a season, or nil with too few tournaments
+  def performance_index(tournaments)
+    return if tournaments.size < performance_settings[:min_tournaments]
+
+    (tournaments.sum { |t| t[:score] } / tournaments.size).round(3)
+  end
+
+  def performance_settings
+    { method: ENV['PERFORMANCE_METHOD'] || 'percentile',
+      min_tournaments: (ENV['PERFORMANCE_MIN'] || 2).to_i }
+  end
+
+  # how strong each tournament's field was, from how the attending schools did
+  # the season before: the mean of their performance indices, or when none of
+  # them has one, the mean number of tournaments they went to that season --
+  # {filename => {value:, basis:}}, or nil when no school has any history --
+  # taking the output of school_seasons, which config.rb works out once
+  def strength_of_field(interpreters, seasons)
+    interpreters.transform_values do |i|
+      prior = i.teams.reject(&:exhibition?)
+               .map { |t| school_slug(full_school_name(t)) }.uniq
+               .map { |slug| seasons[slug][:seasons][i.tournament.year - 1] }
+      indices = prior.compact.map { |s| s[:performance_index] }.compact
+      if indices.any?
+        { value: (indices.sum / indices.size).round(3),
+          basis: 'performance_index' }
+      elsif prior.any?
+        counts = prior.map { |s| s ? s[:tournaments].size : 0 }
+        { value: counts.sum.fdiv(counts.size).round(3), basis: 'appearances' }
+      end
+    end
+  end
+
+  # the schools with the highest performance index in one division and season,
+  # best first, from the output of school_seasons
+  def leaderboard(seasons, year, division, top: 10)
+    seasons.filter_map do |slug, school|
+      index = school[:seasons].dig(year, :divisions, division,
+                                   :performance_index)
+      { name: school[:name], slug: slug, index: index } if index
+    end.sort_by { |s| [-s[:index], s[:name]] }.first(top)
+  end
+
+  # every tournament re-scored with each scheme in data/scoring_schemes.yaml,
+  # plus per-season totals of each school's best team at each tournament, along
+  # with how strong the fields it faced were on average (given the output of
+  # strength_of_field)
+  def weighted_rankings(interpreters, strengths = {})
+    data.scoring_schemes.to_h.map do |name, scheme|
+      tournaments = interpreters.transform_values do |i|
+        weighted_standings(i, scheme)
+      end
+      seasons = weighted_seasons(interpreters, tournaments, scheme, strengths)
+      [name, { tournaments: tournaments, seasons: seasons }]
+    end.to_h
+  end
+
+  # [{team:, points:}] for the non-exhibition teams of a tournament, best first
+  def weighted_standings(interpreter, scheme)
+    higher = scheme['higher_is_better']
+    events = interpreter.events.reject { |e| e.trial? || e.trialed? }
+    interpreter.teams.reject(&:exhibition?).map do |team|
+      scores = events.map do |e|
+        weighted_points(scheme, team.placing_for(e))
+      end.sort
+      scores.reverse! unless higher
+      points = scores.drop(scheme['drop_lowest'] || 0).sum
+      points += team.penalties.sum(&:points) if scheme['points'] == 'place'
+      { team: team, points: points }
+    end.sort_by.with_index { |s, n| [higher ? -s[:points] : s[:points], n] }
+  end
+
+  def weighted_points(scheme, placing)
+    return placing.points if scheme['points'] == 'place'
+
+    points_for = lambda do |place|
+      scheme['points'].fetch(place.to_s, scheme['other'] || 0)
+    end
+    return points_for.call(nil) if placing.place.nil?
+    return points_for.call(placing.place) unless placing.tie? &&
+                                                 scheme['ties'] == 'split'
+
+    tied = placing.event.placings.count { |p| p.place == placing.place }
+    (placing.place...placing.place + tied).sum(&points_for).fdiv(tied)
+  end
+
+  # {year => [{school:, points:, tournaments:, strength_of_field:}]}, best
+  # first -- totals only make sense to compare for higher_is_better schemes,
+  # since with place-based ones attending more tournaments adds points, and
+  # strength_of_field is the mean over the tournaments that have one (or nil)
+  def weighted_seasons(interpreters, tournaments, scheme, strengths = {})
+    seasons = Hash.new do |h, k|
+      h[k] = Hash.new do |s, n|
+        s[n] = { school: n, points: 0, tournaments: 0, fields: [] }
+      end
+    end
+    tournaments.each do |filename, standings|
+      year = interpreters[filename].tournament.year
+      standings.uniq { |s| full_school_name(s[:team]) }.each do |s|
+        totals = seasons[year][full_school_name(s[:team])]
+        totals[:points] += s[:points]
+        totals[:tournaments] += 1
+        totals[:fields] << strengths[filename]&.fetch(:value)
+      end
+    end
+    seasons.sort.map do |year, totals|
+      [year, totals.values.map do |s|
+        fields = s.delete(:fields).compact
+        mean = fields.sum.fdiv(fields.size).round(3) unless fields.empty?
+        s.merge(strength_of_field: mean)
+      end.sort_by do |s|
+        scheme['higher_is_better'] ? -s[:points] : s[:points]
+      end]
+    end.to_h
+  end
+
+  def json_rankings(interpreters, strengths)
+    JSON.pretty_generate(
+      weighted_rankings(interpreters, strengths).transform_values do |rankings|
+        rankings.merge(
+          tournaments: rankings[:tournaments].transform_values do |standings|
+            standings.map do |s|
+              { team: full_team_name(s[:team]), points: s[:points] }
+            end
+          end
+        )
+      end
+    )
+  end
+
+  # CSV.generate for every CSV export, quoting every field if CSV_QUOTE_ALL is
+  # set and writing missing values (e.g. a school without a city) as CSV_NULL if
+  # it is set, so they can be told apart from empty strings
+  def generate_csv
+    rows = []
+    yield rows
+    null = ENV['CSV_NULL']
+    CSV.generate(force_quotes: !ENV['CSV_QUOTE_ALL'].nil?) do |csv|
+      rows.each { |row| csv << row.map { |v| v.nil? ? null : v } }
+    end
+  end
+
+  def csv_rankings(interpreters)
+    generate_csv do |csv|
+      weighted_rankings(interpreters).each do |name, rankings|
+        rankings[:tournaments].each do |filename, standings|
+          standings.each.with_index(1) do |s, rank|
+            csv << [name, filename, rank, full_team_name(s[:team]), s[:points]]
+          end
+        end
+      end
+    end
+  end
+
+  # schools at a tournament in alphabetical order (ignoring case, then by city),
+  # each with the numbers of all the teams it fielded
+  def attendance(interpreter)
+    interpreter
+      .teams
+      .group_by { |t| [t.school, t.city, t.state] }
+      .map do |(school, city, state), teams|
+        { school: school, city: city, state: state,
+          numbers: teams.map(&:number).sort,
+          exhibition: teams.any?(&:exhibition?) }
+      end
+      .sort_by { |s| [s[:school].downcase, s[:city].to_s.downcase, s[:state]] }
+  end
+
+  def csv_attendance(interpreter)
+    generate_csv do |csv|
+      csv << %w[School City State Teams Exhibition]
+      attendance(interpreter).each do |s|
+        csv << [s[:school], s[:city], s[:state], s[:numbers].join(' '),
+                s[:exhibition] ? 'yes' : 'no']
+      end
+    end
+  end
+
+  # every team at the given tournaments, by tournament and then team number
+  def team_numbers(interpreters)
+    interpreters.sort.flat_map do |filename, i|
+      i.teams.sort_by(&:number).map do |t|
+        { tournament: filename, number: t.number, school: t.school,
+          city: t.city, state: t.state, exhibition: t.exhibition? }
+      end
+    end
+  end
+
+  def csv_team_numbers(interpreters)
+    generate_csv do |csv|
+      csv << %w[Tournament Number School City State Exhibition]
+      team_numbers(interpreters).each do |t|
+        csv << [*t.values_at(:tournament, :number, :school, :city, :state),
+                t[:exhibition] ? 'yes' : 'no']
+      end
+    end
+  end
+
+  # SciolyFF's tiebreakers for teams with the same points, in the order it
+  # applies them
+  TIEBREAKERS ||= %i[medal_counts trial_event_points
+                     trial_event_medal_counts].freeze
+
+  # how a team was ranked against the others with the same points, for
+  # downstream rankers to follow: the ranks it was tied with, and the first
+  # tiebreaker that differs from the tied team next to it in the standings
+  # (below it, or above it if it is last of the tie) -- 'unknown' if none do
+  def tiebreak(team, teams)
+    tied = teams.select { |t| t.points == team.points } - [team]
+    return nil if tied.empty?
+
+    neighbor = tied.find { |t| t.rank > team.rank } || tied.last
+    rule = TIEBREAKERS.find do |method|
+      team.respond_to?(method) &&
+        team.public_send(method) != neighbor.public_send(method)
+    end
+    return 'unknown' unless rule
+
+    { tied_with: tied.map(&:rank), rule: rule.to_s,
+      team: team.public_send(rule), neighbor: neighbor.public_send(rule) }
+  end
+
+  # top three of every tournament, and of each of its subdivisions if it has any
+  def podiums(interpreters)
+    top_three = lambda do |i|
+      teams = i.teams.reject(&:exhibition?)
+      teams.first(3).map do |t|
+        { rank: t.rank, team: full_team_name(t), points: t.points,
+          tiebreak: tiebreak(t, teams) }.compact
+      end
+    end
+    interpreters.transform_values do |i|
+      podium = { overall: top_three.call(i) }
+      if i.tournament.subdivisions?
+        podium[:subdivisions] = i.subdivisions.transform_values(&top_three)
+      end
+      podium
+    end
+  end
+
+  def map_url(location)
+    latitude, longitude = location
+    "https://www.openstreetmap.org/?mlat=#{latitude}&mlon=#{longitude}"\
+      "#map=15/#{latitude}/#{longitude}"
+  end
+
+  # iCalendar feed of a school's likely tournaments as all-day tentative
+  # events, each linking to its results (or placeholder) page
+  def ics_calendar(school, tournaments)
+    esc = lambda do |text|
+      text.to_s.gsub(/[\\;,]/) { |c| "\\#{c}" }.gsub("\n") { '\n' }
+    end
+    lines = ['BEGIN:VCALENDAR', 'VERSION:2.0',
+             'PRODID:-//Unosmium//Tournament feeds//EN',
+             "X-WR-CALNAME:#{esc.call(school)} tournaments"]
+    tournaments.each do |t|
+      lines.push('BEGIN:VEVENT',
+                 "UID:#{t[:page]}@unosmium.org",
+                 "DTSTAMP:#{t[:date].strftime('%Y%m%d')}T000000Z",
+                 "DTSTART;VALUE=DATE:#{t[:date].strftime('%Y%m%d')}",
+                 "DTEND;VALUE=DATE:#{t[:date].next_day.strftime('%Y%m%d')}",
+                 "SUMMARY:#{esc.call(t[:name])}",
+                 ("LOCATION:#{esc.call(t[:location])}" if t[:location]),
+                 "URL:https://unosmium.org/results/#{t[:page]}.html",
+                 'STATUS:TENTATIVE',
+                 'END:VEVENT')
+    end
+    lines << 'END:VCALENDAR'
+    lines.compact.map { |line| "#{line}\r\n" }.join
+  end
+
+  # GeoJSON points for the tournaments with known coordinates, with what the
+  # front-end map needs for styling markers and linking to the results
+  def locations_geojson(interpreters, locations)
+    features = interpreters.select { |f, _| locations.key?(f) }.map do |f, i|
+      latitude, longitude = locations[f]
+      {
+        type: 'Feature',
+        geometry: { type: 'Point', coordinates: [longitude, latitude] },
+        properties: {
+          name: "#{i.tournament.year} #{tournament_title(i.tournament)}",
+          season: i.tournament.year,
+          division: i.tournament.division,
+          teams: i.tournament.nonexhibition_teams_count,
+          url: "/results/#{page_name(f)}.html"
+        }
+      }
+    end
+    { type: 'FeatureCollection', features: features }
+  end
+
   def csv_schools(interpreters)
-    CSV.generate do |csv|
+    generate_csv do |csv|
       interpreters
         .values
         .flat_map { |i| i.teams.map {|t| [t.school, t.city, t.state] }}
         .uniq
         .sort_by { |t| [t[0], t[1].nil? ? '' : t[1], t[2]] }
-        .each { |row| csv << row }
+        .each { |row| csv << [*row, row[1].nil? ? 'no' : 'yes'] }
     end
   end
 
   def csv_events(interpreters)
-    CSV.generate do |csv|
+    generate_csv do |csv|
       interpreters
         .values
         .flat_map { |i| i.events.map {|e| [e.name] }}
@@ -305,6 +1026,394 @@ module CustomHelpers
     end
   end
 
+  # best raw score and longest winning streak by one school for each event and
+  # division, leaving out trial events and exhibition teams -- whether a low
+  # score is better comes from the results files, unless data/event_scoring.yaml
+  # says otherwise for the event (scores from different tests aren't really
+  # comparable, so this is more of a curiosity)
+  def event_records(interpreters)
+    overrides = (data.event_scoring || {}).to_h
+    latest = interpreters.values.map { |i| i.tournament.year }.max
+    by_event = Hash.new { |h, k| h[k] = [] }
+    interpreters.each do |filename, i|
+      i.events.reject { |e| e.trial? || e.trialed? }.each do |e|
+        by_event[[e.name, i.tournament.division]] << [filename, i, e]
+      end
+    end
+    by_event.sort.map do |(name, division), runs|
+      runs = runs.sort_by { |_, i, _| i.tournament.date }
+      low = overrides.key?(name) ? overrides[name] == 'low' : nil
+      { event: name, division: division,
+        best_score: best_raw_score(runs, low, latest),
+        streak: longest_streak(runs, latest) }
+    end
+  end
+
+  def best_raw_score(runs, low, latest)
+    scores = runs.flat_map do |filename, i, e|
+      lower = low.nil? ? e.low_score_wins? : low
+      e.placings.select { |p| p.raw? && !p.team.exhibition? }.map do |p|
+        { score: p.raw.score, lower: lower, school: full_school_name(p.team),
+          file: filename, date: i.tournament.date,
+          new: i.tournament.year == latest }
+      end
+    end
+    return if scores.empty?
+
+    # the direction of the first tournament wins if results files disagree
+    lower = scores.first[:lower]
+    best = scores.min_by { |s| lower ? s[:score] : -s[:score] }
+    best.reject { |k, _| k == :lower }.merge(lower_is_better: lower)
+  end
+
+  # most tournaments in a row (in date order) at which the same school won
+  def longest_streak(runs, latest)
+    best = nil
+    current = nil
+    runs.each do |filename, i, e|
+      winners = e.placings.select { |p| p.place == 1 && !p.team.exhibition? }
+                 .map { |p| full_school_name(p.team) }
+      school = winners.first if winners.uniq.size == 1
+      current = if school && current && current[:school] == school
+                  current.merge(length: current[:length] + 1, to: filename)
+                elsif school
+                  { school: school, length: 1, from: filename, to: filename }
+                end
+      next unless current && (best.nil? || current[:length] > best[:length])
+
+      best = current.merge(new: i.tournament.year == latest)
+    end
+    best if best && best[:length] > 1
+  end
+
+  # id of the lineage in data/event_lineage.yaml that an event name belongs to
+  # in a season, if any
+  def event_lineage(name, year)
+    data.event_lineage.to_h.each do |id, names|
+      match = names.any? do |entry|
+        entry['name'] == name && entry['from'] <= year &&
+          (entry['to'].nil? || year <= entry['to'])
+      end
+      return id.to_s if match
+    end
+    nil
+  end
+
+  # whether an event was official in a division that season according to
+  # data/official_events.yaml, or nil if the event isn't listed for it
+  def official_event?(name, division, year)
+    ranges = data.official_events.to_h.dig(name, division)
+    ranges&.any? { |r| (r['from']..r['to']).cover?(year) }
+  end
+
+  # every event and division in the results, with the seasons it was official
+  # (e.g. 2016-2017; 2019-) next to the seasons it appears in the results, and
+  # the seasons it was held as a regular event while not official
+  def csv_events_annotated(interpreters)
+    seen = Hash.new { |h, key| h[key] = { all: [], regular: [] } }
+    interpreters.each_value do |i|
+      i.events.each do |e|
+        years = seen[[e.name, i.tournament.division]]
+        years[:all] << i.tournament.year
+        years[:regular] << i.tournament.year unless e.trial?
+      end
+    end
+    generate_csv do |csv|
+      csv << ['Event', 'Division', 'Official seasons', 'First seen',
+              'Last seen', 'Unofficial seasons']
+      seen.sort_by { |(name, division), _| [name.downcase, division] }
+          .each do |(name, division), years|
+        ranges = data.official_events.to_h.dig(name, division)
+        unofficial = years[:regular].uniq.sort.reject do |year|
+          official_event?(name, division, year) != false
+        end
+        csv << [name, division,
+                ranges&.map { |r| "#{r['from']}-#{r['to']}" }&.join('; '),
+                years[:all].min, years[:all].max,
+                (unofficial.join('; ') unless ranges.nil?)]
+      end
+    end
+  end
+
+  # schools of a division by overall championships and then podium finishes
+  # (by their best non-exhibition team at each tournament), leaving out schools
+  # with fewer than ALL_TIME_MIN tournaments -- schools with the same record
+  # share a position, e.g. 1, 1, 3
+  def all_time_leaderboard(interpreters, division)
+    records = Hash.new do |h, school|
+      h[school] = { championships: 0, podiums: 0, appearances: 0 }
+    end
+    interpreters.each_value do |i|
+      next unless i.tournament.division == division
+
+      i.teams.reject(&:exhibition?).group_by { |t| full_school_name(t) }
+       .each do |school, teams|
+        rank = teams.map(&:rank).min
+        records[school][:appearances] += 1
+        records[school][:championships] += 1 if rank == 1
+        records[school][:podiums] += 1 if rank <= 3
+      end
+    end
+    min = (ENV['ALL_TIME_MIN'] || '3').to_i
+    rows = records.select { |_, r| r[:appearances] >= min }
+                  .sort_by do |school, r|
+                    [-r[:championships], -r[:podiums], -r[:appearances],
+                     school]
+                  end
+    rows.each_with_index.map do |(school, record), index|
+      tied = rows.index do |_, other|
+        other.values_at(:championships, :podiums) ==
+          record.values_at(:championships, :podiums)
+      end
+      { position: (tied || index) + 1, school: school, **record }
+    end
+  end
+
+  def csv_all_time_leaderboard(interpreters, division)
+    generate_csv do |csv|
+      csv << %w[Position School Championships Podiums Appearances]
+      all_time_leaderboard(interpreters, division).each do |row|
+        csv << row.values_at(:position, :school, :championships, :podiums,
+                             :appearances)
+      end
+    end
+  end
+
+  # per season, how many tournaments of each division offered each event (by
+  # name), with trial events counted separately from the rest
+  def events_by_division(interpreters)
+    seasons = Hash.new do |h, year|
+      h[year] = Hash.new do |events, name|
+        events[name] = Hash.new { |d, div| d[div] = { count: 0, trial: 0 } }
+      end
+    end
+    interpreters.each_value do |i|
+      i.events.each do |e|
+        counts = seasons[i.tournament.year][e.name][i.tournament.division]
+        counts[e.trial? ? :trial : :count] += 1
+      end
+    end
+    seasons.sort.reverse.map do |year, events|
+      [year, events.sort_by { |name, _| name.downcase }.to_h]
+    end.to_h
+  end
+
+  def csv_events_by_division(interpreters)
+    generate_csv do |csv|
+      csv << %w[Season Event Division Tournaments Trial Lineage]
+      events_by_division(interpreters).each do |year, events|
+        events.each do |name, divisions|
+          divisions.sort.each do |division, counts|
+            csv << [year, name, division, counts[:count], counts[:trial],
+                    event_lineage(name, year)]
+          end
+        end
+      end
+    end
+  end
+
+  # renders curator notes written in Markdown, keeping only a small set of
+  # formatting tags and links to http(s) URLs so that a results file can't
+  # inject arbitrary markup into the page
+  def render_notes(markdown)
+    return '' if markdown.to_s.strip.empty?
+
+    Kramdown::Document
+      .new(ERB::Util.html_escape(markdown.to_s.strip), auto_ids: false)
+      .to_html
+      .gsub(%r{<(/?)(\w+)([^>]*)>}) do
+        closing, tag, attributes = Regexp.last_match.captures
+        next '' unless NOTES_ALLOWED_TAGS.include?(tag)
+        next "</#{tag}>" unless closing.empty?
+        next "<#{tag}>" unless tag == 'a'
+
+        href = attributes[/href="(https?:[^"]*)"/, 1]
+        href ? %(<a href="#{href}">) : '<a>'
+      end
+      .strip
+  end
+
+  # where a set of results came from, from the optional `official`,
+  # `submitted_by`, and `source_url` keys in its unosmium block
+  def provenance_notes(ext)
+    parts = []
+    unless ext[:official].nil?
+      parts << (ext[:official] ? 'Official results' : 'Unofficial results')
+    end
+    if ext[:submitted_by]
+      parts << "submitted by #{ERB::Util.html_escape(ext[:submitted_by])}"
+    end
+    if http_url?(ext[:source_url])
+      url = ERB::Util.html_escape(ext[:source_url])
+      parts << %(from <a href="#{url}">the original source</a>)
+    end
+    parts.join(', ').sub(/\A./, &:upcase)
+  end
+
+  # the optional event schedule in a results file's unosmium block, in order of
+  # start time -- unquoted times like 9:00 are read by YAML as minutes past
+  # midnight, so they are turned back into H:MM
+  def event_schedule(ext)
+    clock = lambda do |time|
+      time.is_a?(Integer) ? format('%d:%02d', time / 60, time % 60) : time.to_s
+    end
+    Array(ext[:schedule]).select { |slot| slot.is_a?(Hash) && slot[:event] }
+                         .map do |slot|
+      { event: slot[:event].to_s, start: clock.call(slot[:start]),
+        end: (clock.call(slot[:end]) if slot[:end]),
+        location: slot[:location]&.to_s }
+    end.sort_by { |slot| slot[:start].rjust(5, '0') }
+  end
+
+  def http_url?(url)
+    uri = URI.parse(url.to_s)
+    uri.is_a?(URI::HTTP) && !uri.host.to_s.empty?
+  rescue URI::InvalidURIError
+    false
+  end
+
+  # teams by events grid of placings as an inline SVG, each cell colored from
+  # green for first to red for last with whichever of white or black text is
+  # legible on it, under a header in the tournament's theme color
+  def placement_heatmap(interpreter, theme)
+    cell_w = 28
+    cell_h = 18
+    label_w = 220
+    header_h = 150
+    events = interpreter.events
+    teams = interpreter.teams.first(HEATMAP_MAX_ROWS)
+    last = [interpreter.teams.count - 1, 1].max
+    width = label_w + cell_w * events.count
+    height = header_h + cell_h * teams.count
+    esc = ->(text) { ERB::Util.html_escape(text) }
+
+    svg = [%(<svg xmlns="http://www.w3.org/2000/svg" class="heatmap" ) +
+           %(role="img" width="#{width}" height="#{height}" ) +
+           %(viewBox="0 0 #{width} #{height}" font-size="11">),
+           '<title>Placings by team and event</title>',
+           %(<rect width="#{width}" height="#{header_h}" fill="#{theme}"/>)]
+    events.each_with_index do |e, col|
+      x = label_w + cell_w * col + cell_w / 2
+      svg << %(<text x="#{x}" y="#{header_h - 6}" fill="white" ) +
+             %(transform="rotate(-60 #{x} #{header_h - 6})">) +
+             "#{esc.call(e.name)}</text>"
+    end
+    teams.each_with_index do |team, row|
+      y = header_h + cell_h * row
+      name = "#{team.rank}. #{format_school(team)} #{team.suffix}".strip
+      svg << %(<text x="4" y="#{y + 13}">) +
+             "#{esc.call(name[0, 34])}</text>"
+      events.each_with_index do |e, col|
+        place = e.placing_for(team)&.place
+        x = label_w + cell_w * col
+        unless place
+          svg << %(<rect x="#{x}" y="#{y}" width="#{cell_w}" ) +
+                 %(height="#{cell_h}" fill="#e0e0e0"/>)
+          next
+        end
+
+        share = [(place - 1).to_f / last, 1.0].min
+        color = "hsl(#{(120 * (1 - share)).round}, 65%, 45%)".paint
+        text = wcag2_contrast(color) >= 4.5 ? 'white' : 'black'
+        svg << %(<rect x="#{x}" y="#{y}" width="#{cell_w}" ) +
+               %(height="#{cell_h}" fill="#{color.to_hex}"/>)
+        svg << %(<text x="#{x + cell_w / 2}" y="#{y + 13}" fill="#{text}" ) +
+               %(text-anchor="middle">#{place}</text>)
+      end
+    end
+    svg << '</svg>'
+    svg.join("\n")
+  end
+
+  # histogram of an event's raw scores as a small inline SVG, with more bins
+  # for bigger tournaments and the bar holding the winning score in the theme
+  # color -- scores run from worst to best left to right, so the axis is
+  # flipped for events where the lowest score wins
+  def score_histogram(event, theme)
+    scores = event.placings.select(&:raw?).map { |p| p.raw.score.to_f }
+    return '' if scores.size < 2
+
+    width = 240
+    height = 80
+    label_h = 14
+    counts, ends = histogram_bins(scores, event.low_score_wins?)
+    bins = counts.size
+    show = ->(score) { (score % 1).zero? ? score.to_i : score.round(2) }
+    bar_w = width.to_f / bins
+    bar_h = (height - label_h * 2).to_f / counts.max
+
+    svg = [%(<svg xmlns="http://www.w3.org/2000/svg" class="histogram" ) +
+           %(role="img" width="#{width}" height="#{height}" ) +
+           %(viewBox="0 0 #{width} #{height}" font-size="10">),
+           "<title>#{ERB::Util.html_escape(event.name)} scores, winning " \
+           "score #{show.call(ends[1])}</title>"]
+    counts.each_with_index do |count, bin|
+      h = (bar_h * count).round(1)
+      fill = bin == bins - 1 ? theme : '#9e9e9e'
+      svg << %(<rect x="#{(bar_w * bin).round(1)}" ) +
+             %(y="#{(height - label_h - h).round(1)}" ) +
+             %(width="#{(bar_w - 1).round(1)}" height="#{h}" fill="#{fill}"/>)
+    end
+    svg << %(<text x="0" y="#{height - 2}">#{show.call(ends[0])}</text>)
+    svg << %(<text x="#{width}" y="#{height - 2}" text-anchor="end">) +
+           "#{show.call(ends[1])}</text>"
+    svg << '</svg>'
+    svg.join("\n")
+  end
+
+  # counts of scores in each histogram bin, worst to best, and the worst and
+  # best score -- sqrt(n) bins between 4 and 12, or one bin if every score is
+  # the same, with the best score in the last bin (checked by
+  # scripts/check-histograms.rb)
+  def histogram_bins(scores, low_score_wins)
+    low, high = scores.minmax
+    bins = high > low ? Math.sqrt(scores.size).ceil.clamp(4, 12) : 1
+    size = high > low ? (high - low) / bins : 1.0
+    counts = Array.new(bins, 0)
+    scores.each { |s| counts[[((s - low) / size).floor, bins - 1].min] += 1 }
+    ends = [low, high]
+    return [counts, ends] unless low_score_wins
+
+    [counts.reverse, ends.reverse]
+  end
+
+  # spreadsheet version of a tournament's results table, with the same
+  # superscript markers as the HTML, prefixed with a byte order mark for Excel
+  # if CSV_BOM is set
+  def csv_results(interpreter)
+    csv = generate_csv do |rows|
+      rows << ['Rank', 'Team', 'School', 'City', 'State', 'Total'] +
+              interpreter.events.map(&:name) + ['Team Penalties', 'Notes']
+      interpreter.teams.each do |team|
+        rows << [
+          "#{team.rank}#{'✧' if team.earned_bid?}",
+          team.number,
+          [team.school, team.suffix].compact.join(' '),
+          team.city,
+          team.state,
+          team.points,
+          *interpreter.events.map { |e| csv_placing(e.placing_for(team)) },
+          team.penalties.sum(&:points),
+          team_notes(team)
+        ]
+      end
+    end
+    ENV['CSV_BOM'] ? "\uFEFF#{csv}" : csv
+  end
+
+  def csv_placing(placing)
+    unknown = placing.unknown? && !placing.points_limited_by_maximum_place?
+    points = unknown ? '??' : placing.isolated_points.to_s
+    points + sup_tag(placing).gsub(%r{</?sup>}, '')
+  end
+
+  def team_notes(team)
+    if team.disqualified? then 'Disqualified'
+    elsif team.exhibition? && !team_attended?(team) then 'Absent'
+    elsif team.exhibition? then 'Exhibition'
+    end
+  end
+
   def placing_notes(placing)
     place = placing.place
     points = placing.isolated_points
@@ -0,0 +1,36 @@
+#!/usr/bin/env ruby
+# frozen_string_literal: true
+
+# Pins the binning behind the score histograms on results pages (see
+# histogram_bins in helpers/custom_helpers.rb): how many bins there are, which
+# bin each score falls in, and which way round they go when the lowest score
+# wins. Needs the site's gems, so run it as
+#
+#   bundle exec ruby scripts/check-histograms.rb
+
+require 'pathname'
+require_relative '../helpers/custom_helpers'
+
+helper_methods = Object.new.extend(CustomHelpers)
+cases = {
+  'four evenly spread scores get the minimum of 4 bins' =>
+    [[0, 1, 2, 3], false, [[1, 1, 1, 1], [0, 3]]],
+  'the same scores with the lowest winning are flipped' =>
+    [[0, 1, 2, 3], true, [[1, 1, 1, 1], [3, 0]]],
+  'identical scores share a single bin' =>
+    [[10, 10, 10, 10, 10], false, [[5], [10, 10]]],
+  '100 scores get sqrt(100) = 10 bins, the best in the last' =>
+    [(1..100).to_a, false, [[10] * 10, [1, 100]]],
+  'the best score stays last when the lowest wins' =>
+    [[1, 2, 2, 9, 10], true, [[2, 0, 0, 3], [10, 1]]]
+}
+failures = cases.reject do |_, (scores, low_wins, expected)|
+  counts, ends = helper_methods.histogram_bins(scores.map(&:to_f), low_wins)
+  counts == expected[0] && ends == expected[1].map(&:to_f)
+end.keys
+bins = helper_methods.histogram_bins((0...400).map(&:to_f), false)[0].size
+failures << '400 scores are capped at 12 bins' unless bins == 12
+
+failures.each { |description| puts "failed: #{description}" }
+puts "#{cases.size + 1 - failures.size} of #{cases.size + 1} checks passed"
+exit(failures.empty? ? 0 : 1)
@@ -0,0 +1,128 @@
+#!/usr/bin/env ruby
+# frozen_string_literal: true
+
+# Pins the numbers behind the rankings on the site for the small fixture
+# seasons in scripts/fixtures/ (three Division C tournaments over 2019 and 2020,
+# worked out by hand): the standings scoring scheme reproducing the overall
+# standings, a medal scheme splitting ties, the performance index and the rest
+# of school_seasons, strength of field, and the leaderboards. Needs the site's
+# gems, so run it as
+#
+#   bundle exec ruby scripts/check-rankings.rb
+
+require 'active_support/core_ext/integer/inflections'
+require 'pathname'
+require 'sciolyff/interpreter'
+require 'yaml'
+require_relative '../helpers/custom_helpers'
+
+%w[PERFORMANCE_METHOD PERFORMANCE_MIN ALL_TIME_MIN INCLUDE_TRIAL_MEDALS]
+  .each { |name| ENV.delete(name) }
+
+interpreters = Dir[File.join(__dir__, 'fixtures', '*.yaml')].sort.to_h do |path|
+  [File.basename(path, '.yaml'), SciolyFF::Interpreter.new(File.read(path))]
+end
+invitational, regional, next_invitational = interpreters.keys
+
+# the schemes in data/scoring_schemes.yaml with string keys, as Middleman reads
+# them, and one giving medal points to check split ties with
+schemes_file = File.join(__dir__, '..', 'data', 'scoring_schemes.yaml')
+schemes = YAML.safe_load(File.read(schemes_file))
+schemes['medals'] = { 'points' => { '1' => 3, '2' => 2, '3' => 1 },
+                      'ties' => 'split', 'higher_is_better' => true }
+helper_methods = Object.new.extend(CustomHelpers)
+site_data = Struct.new(:scoring_schemes).new(schemes)
+helper_methods.define_singleton_method(:data) { site_data }
+
+school = ->(name) { "#{name} High School (OH)" }
+slug = ->(name) { "#{name}_High_School_(OH)" }
+seasons = helper_methods.school_seasons(interpreters)
+season = ->(name, year) { seasons.dig(slug.call(name), :seasons, year) }
+strengths = helper_methods.strength_of_field(interpreters, seasons)
+rankings = helper_methods.weighted_rankings(interpreters, strengths)
+totals = lambda do |name, year|
+  rankings.dig(name, :seasons, year).map { |s| [s[:school], s[:points]] }
+end
+positions = lambda do
+  helper_methods.all_time_leaderboard(interpreters, 'C')
+                .map { |row| [row[:position], row[:school]] }
+end
+
+cases = {
+  'the standings scheme reproduces every overall standings table' =>
+    [interpreters.map do |filename, _|
+      rankings.dig('standings', :tournaments, filename)
+              .map { |s| [s[:team], s[:points]] }
+    end,
+     interpreters.values.map do |i|
+       i.teams.sort_by(&:rank).map { |t| [t, t.points] }
+     end],
+  'split ties share out the points of the places they span' =>
+    [rankings.dig('medals', :tournaments, invitational).map { |s| s[:points] },
+     [6, 3.5, 2.5]],
+  'season totals add up each school at every tournament' =>
+    [totals.call('medals', 2019),
+     [[school.call('Alpha'), 10], [school.call('Beta'), 9.5],
+      [school.call('Gamma'), 2.5], [school.call('Delta'), 2],
+      [school.call('Zeta'), 0]]],
+  'season totals carry the mean strength of the fields faced' =>
+    [rankings.dig('medals', :seasons, 2020).map { |s| s[:strength_of_field] },
+     [0.583] * 4],
+  'finish scores run from 1 for first to 0 for last' =>
+    [season.call('Gamma', 2019)[:tournaments].map { |t| t[:score] },
+     [0.0, 0.25]],
+  'a season sums up ranks, medals and the performance index' =>
+    [season.call('Alpha', 2019)
+           .slice(:average_rank, :medals, :performance_index),
+     { average_rank: 1.5, medals: [2, 2, 0], performance_index: 0.875 }],
+  'tied event places count as medals for every team tied' =>
+    [season.call('Gamma', 2019)[:medals], [0, 1, 1]],
+  'the performance index is also split by division' =>
+    [season.call('Beta', 2019)[:divisions],
+     { 'C' => { performance_index: 0.75, medals: [2, 2, 0] } }],
+  'a season of a single tournament has no performance index' =>
+    [[season.call('Delta', 2019), season.call('Alpha', 2020)]
+      .map { |s| s[:performance_index] }, [nil, nil]],
+  'the season leaderboard goes by performance index' =>
+    [helper_methods.leaderboard(seasons, 2019, 'C')
+                   .map { |s| [s[:name], s[:index]] },
+     [[school.call('Alpha'), 0.875], [school.call('Beta'), 0.75],
+      [school.call('Gamma'), 0.125]]],
+  'strength of field is the mean prior performance index' =>
+    [strengths[next_invitational],
+     { value: 0.583, basis: 'performance_index' }],
+  'a field without any prior season has no strength' =>
+    [strengths.values_at(invitational, regional), [nil, nil]],
+  'the all-time leaderboard leaves out schools with under 3 tournaments' =>
+    [positions.call,
+     [[1, school.call('Alpha')], [2, school.call('Beta')],
+      [3, school.call('Gamma')]]]
+}
+
+ENV['PERFORMANCE_MIN'] = '3'
+few = helper_methods.school_seasons(interpreters)
+cases['without performance indices, strength goes by appearances'] =
+  [helper_methods.strength_of_field(interpreters, few)[next_invitational],
+   { value: 1.5, basis: 'appearances' }]
+ENV.delete('PERFORMANCE_MIN')
+
+ENV['ALL_TIME_MIN'] = '1'
+cases['schools with the same record share an all-time position'] =
+  [positions.call,
+   [[1, school.call('Alpha')], [2, school.call('Beta')],
+    [3, school.call('Gamma')], [4, school.call('Delta')],
+    [4, school.call('Epsilon')], [6, school.call('Zeta')]]]
+ENV.delete('ALL_TIME_MIN')
+
+ENV['PERFORMANCE_METHOD'] = 'zscore'
+cases['z-scores count standard deviations above the middle rank'] =
+  [[[1, 3], [1, 5], [3, 5]].map { |r| helper_methods.finish_score(*r) },
+   [1.225, 1.414, 0.0]]
+ENV.delete('PERFORMANCE_METHOD')
+
+failures = cases.reject { |_, (actual, expected)| actual == expected }
+failures.each do |description, (actual, _)|
+  puts "failed: #{description} (got #{actual.inspect})"
+end
+puts "#{cases.size - failures.size} of #{cases.size} checks passed"
+exit(failures.empty? ? 0 : 1)
@@ -0,0 +1,59 @@
+#!/bin/sh
+
+# Downloads the tournament logos listed in logo_sources.txt that aren't already
+# in source/images/logos (or all of them, with --refresh), so that
+# find_logo_path picks them up. Each line of logo_sources.txt is a logo name
+# following the usual naming convention, e.g. 2020_mit_invitational_c, and the
+# URL to download it from. Failed downloads are only warned about, since the
+# tournament just keeps using its current (or the default) logo.
+
+root="$(git rev-parse --show-toplevel)"
+logos="$root/source/images/logos"
+max_size=5000000 # bytes
+jobs=4
+
+if [ "$1" = "--fetch-one" ]
+then
+  name=$2
+  url=$3
+  tmp="$(mktemp)"
+  trap 'rm -f "$tmp" "$tmp.png"' EXIT
+
+  if ! type=$(curl -sSfL --max-filesize "$max_size" -o "$tmp" \
+                   -w '%{content_type}' "$url")
+  then
+    echo "warning: could not download logo $name from $url" >&2
+    exit 0
+  fi
+
+  case "$type" in
+    image/svg+xml*)
+      rm -f "$logos/$name".*
+      mv "$tmp" "$logos/$name.svg" ;;
+    image/png*)
+      rm -f "$logos/$name".*
+      mv "$tmp" "$logos/$name.png" ;;
+    image/*)
+      # first frame only, in case of animated GIFs
+      if convert "$tmp[0]" "$tmp.png"
+      then
+        rm -f "$logos/$name".*
+        mv "$tmp.png" "$logos/$name.png"
+      else
+        echo "warning: could not convert logo $name to PNG" >&2
+      fi ;;
+    *)
+      echo "warning: logo $name from $url has content type $type" >&2 ;;
+  esac
+  exit 0
+fi
+
+grep -v '^#' "$root/logo_sources.txt" | while read -r name url
+do
+  [ -z "$name" ] && continue
+  if [ "$1" != "--refresh" ] && ls "$logos/$name".* > /dev/null 2>&1
+  then
+    continue
+  fi
+  echo "$name" "$url"
+done | xargs -n 2 -P "$jobs" "$0" --fetch-one
@@ -0,0 +1,41 @@
+#!/bin/sh
+
+# Prepares the tournament photos in source/images/media/<page name>/ for the
+# gallery on each results page: photos larger than max_side pixels on their
+# longest side are downscaled in place, and a JPEG thumbnail of each is written
+# to thumbs/ next to it (unless it is already there, or with --refresh). Needs
+# ImageMagick. Files that aren't JPEG, PNG or WebP images are skipped, and so
+# the build leaves them out too.
+
+root="$(git rev-parse --show-toplevel)"
+media="$root/source/images/media"
+max_side=2400 # pixels
+thumb_side=400 # pixels
+
+for dir in "$media"/*/
+do
+  [ -d "$dir" ] || continue
+  mkdir -p "$dir/thumbs"
+  for image in "$dir"*
+  do
+    [ -f "$image" ] || continue
+    file="$(basename "$image")"
+    [ "$file" = captions.yaml ] && continue
+    case "$file" in
+      *.jpg|*.jpeg|*.JPG|*.JPEG|*.png|*.PNG|*.webp|*.WEBP) ;;
+      *)
+        echo "warning: skipping $image, which is not a JPEG, PNG or WebP" >&2
+        continue ;;
+    esac
+
+    thumb="$dir/thumbs/${file%.*}.jpg"
+    if [ "$1" != "--refresh" ] && [ -e "$thumb" ]
+    then
+      continue
+    fi
+    mogrify -resize "${max_side}x${max_side}>" "$image" &&
+      convert "$image[0]" -resize "${thumb_side}x${thumb_side}>" \
+              -quality 80 "$thumb" ||
+      echo "warning: could not resize $image" >&2
+  done
+done
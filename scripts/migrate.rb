@@ -0,0 +1,84 @@
+#!/usr/bin/env ruby
+# frozen_string_literal: true
+
+# Proposes renames for results files in data/ that don't follow the
+# YYYY-MM-DD_name_division.yaml pattern, using the date and division in the
+# file's Tournament section and the rest of the old name (minus any date and
+# division) as the name. With --apply the files are renamed with git mv,
+# redirects from the old results page URLs are added to netlify.toml so
+# published links keep working, and the references to them in
+# data/recents.yaml and data/upcoming.yaml are updated. Files without a date or
+# name, or that would collide with another file, are listed as needing manual
+# attention instead.
+#
+#   ruby scripts/migrate.rb          # only list the proposed renames
+#   ruby scripts/migrate.rb --apply  # also make them
+
+require 'date'
+require 'yaml'
+
+root = File.expand_path('..', __dir__)
+data = File.join(root, 'data')
+renames = {}
+
+Dir[File.join(data, '*.yaml')].sort.each do |path|
+  old = File.basename(path, '.yaml')
+  next if old.match?(/\A\d{4}-\d{2}-\d{2}_.+_[abc]\z/)
+
+  begin
+    rep = YAML.safe_load(File.read(path), permitted_classes: [Date])
+  rescue Psych::Exception => e
+    puts "needs manual attention: #{old}.yaml (#{e.message})"
+    next
+  end
+  next unless rep.is_a?(Hash) && rep['Tournament'].is_a?(Hash)
+
+  date = rep['Tournament']['date']
+  date = date.is_a?(Date) ? date.iso8601 : date.to_s
+  division = rep['Tournament']['division'].to_s.downcase
+  name = old.gsub(/\d{4}(-\d{2}-\d{2})?/, '').sub(/(\A|_)[abc]\z/i, '')
+            .gsub(/_+/, '_').delete_prefix('_').delete_suffix('_')
+  new = "#{date}_#{name}_#{division}"
+
+  if !date.match?(/\A\d{4}-\d{2}-\d{2}\z/) || name.empty? || division.empty?
+    puts "needs manual attention: #{old}.yaml (no date, name, or division)"
+  elsif File.exist?(File.join(data, "#{new}.yaml")) || renames.value?(new)
+    puts "needs manual attention: #{old}.yaml (#{new}.yaml already taken)"
+  else
+    renames[old] = new
+    puts "#{old}.yaml -> #{new}.yaml"
+  end
+end
+exit unless ARGV.first == '--apply'
+
+moved = renames.select do |old, new|
+  next false unless system('git', '-C', root, 'mv', "data/#{old}.yaml",
+                           "data/#{new}.yaml")
+
+  File.open(File.join(root, 'netlify.toml'), 'a') do |toml|
+    toml.write(<<~TOML)
+
+      [[redirects]]
+      from = "/results/#{old}.html"
+      to = "/results/#{new}.html"
+      status = 301
+    TOML
+  end
+  true
+end
+
+# the lists of recently added (`- <file>.yaml`) and upcoming (`file: <file>`)
+# tournaments are edited line by line, so that their comments are kept
+{ 'recents.yaml' => ['-', '.yaml'], 'upcoming.yaml' => ['file:', ''] }
+  .each do |list, (key, extension)|
+  path = File.join(data, list)
+  lines = File.readlines(path).map do |line|
+    moved.reduce(line) do |current, (old, new)|
+      file = Regexp.escape(old + extension)
+      current.sub(/\A(\s*#{key}\s*)(['"]?)#{file}\2(\s*)\z/) do
+        "#{$1}#{$2}#{new}#{extension}#{$2}#{$3}"
+      end
+    end
+  end
+  File.write(path, lines.join)
+end
@@ -0,0 +1,137 @@
+#!/usr/bin/env ruby
+# frozen_string_literal: true
+
+# Checks the JSON exports of a build against the JSON Schemas published with
+# them under /results/schemas (and /results/schools/schema.json), so that the
+# fields the schemas describe can be relied on. Every build runs these checks
+# after writing the exports, and fails if one doesn't match -- this script runs
+# them on their own, e.g. on a build directory downloaded from a deploy:
+#
+#   scripts/validate-exports.rb [build directory]
+#
+# Only the parts of JSON Schema the schemas here use are understood: type, enum,
+# required, properties, additionalProperties, propertyNames, items, minItems,
+# maxItems, minimum, pattern, anyOf and $ref to their own definitions.
+
+require 'json'
+
+module ExportSchemas
+  # globs of exports under results/ and the schema (under source/results/)
+  # each has to match
+  SCHEMAS = {
+    'manifest.json' => 'schemas/manifest.json',
+    'podiums.json' => 'schemas/podiums.json',
+    'trophies.json' => 'schemas/trophies.json',
+    'records.json' => 'schemas/records.json',
+    'rankings.json' => 'schemas/rankings.json',
+    'schools.json' => 'schools/schema.json',
+    'schools/summary.json' => 'schemas/schools-summary.json',
+    'team-numbers/*.json' => 'schemas/team-numbers.json',
+    'corrections.json' => 'schemas/corrections.json',
+    'locations.geojson' => 'schemas/locations.json',
+    'build-info.json' => 'schemas/build-info.json',
+    'metrics.json' => 'schemas/metrics.json'
+  }.freeze
+
+  TYPES = {
+    'object' => ->(v) { v.is_a?(Hash) },
+    'array' => ->(v) { v.is_a?(Array) },
+    'string' => ->(v) { v.is_a?(String) },
+    'integer' => ->(v) { v.is_a?(Integer) },
+    'number' => ->(v) { v.is_a?(Numeric) },
+    'boolean' => ->(v) { [true, false].include?(v) },
+    'null' => ->(v) { v.nil? }
+  }.freeze
+
+  module_function
+
+  # problems with every export in a build directory, as "file: problem"
+  def check(build_dir, schema_dir)
+    results_dir = File.join(build_dir, 'results')
+    SCHEMAS.flat_map do |pattern, schema_file|
+      schema = JSON.parse(File.read(File.join(schema_dir, schema_file)))
+      Dir[File.join(results_dir, pattern)].sort.flat_map do |path|
+        name = path.delete_prefix("#{build_dir}/")
+        errors(JSON.parse(File.read(path)), schema, schema)
+          .map { |error| "#{name}: #{error}" }
+      rescue JSON::ParserError => e
+        ["#{name}: is not valid JSON (#{e.message.lines.first.strip})"]
+      end
+    end
+  end
+
+  # ways a value doesn't match a schema, each starting with where in the value
+  def errors(value, schema, root, path = '$')
+    if schema['$ref']
+      ref = schema['$ref'].delete_prefix('#/').split('/')
+      return errors(value, root.dig(*ref), root, path)
+    end
+
+    types = Array(schema['type'])
+    unless types.empty? || types.any? { |type| TYPES.fetch(type).call(value) }
+      return ["#{path} is not #{types.join(' or ')}"]
+    end
+    if schema['enum'] && !schema['enum'].include?(value)
+      return ["#{path} is not one of #{schema['enum'].join(', ')}"]
+    end
+    if schema['anyOf']&.none? { |s| errors(value, s, root, path).empty? }
+      return ["#{path} matches none of the shapes it can have"]
+    end
+
+    case value
+    when Hash then object_errors(value, schema, root, path)
+    when Array then array_errors(value, schema, root, path)
+    when String
+      pattern = schema['pattern']
+      return [] if pattern.nil? || value.match?(/#{pattern}/)
+
+      ["#{path} does not match #{pattern}"]
+    when Numeric
+      minimum = schema['minimum']
+      return [] if minimum.nil? || value >= minimum
+
+      ["#{path} is less than #{minimum}"]
+    else []
+    end
+  end
+
+  def object_errors(value, schema, root, path)
+    missing = Array(schema['required']).reject { |key| value.key?(key) }
+                                       .map { |key| "#{path} has no #{key}" }
+    missing + value.flat_map do |key, child|
+      problems = []
+      if schema['propertyNames']
+        problems += errors(key, schema['propertyNames'], root, "#{path} key")
+      end
+      child_schema = schema.fetch('properties', {}).fetch(key) do
+        schema.fetch('additionalProperties', {})
+      end
+      next problems + ["#{path} has unexpected #{key}"] if child_schema == false
+
+      problems + errors(child, child_schema, root, "#{path}.#{key}")
+    end
+  end
+
+  def array_errors(value, schema, root, path)
+    problems = []
+    if schema['minItems'] && value.size < schema['minItems']
+      problems << "#{path} has fewer than #{schema['minItems']} items"
+    end
+    if schema['maxItems'] && value.size > schema['maxItems']
+      problems << "#{path} has more than #{schema['maxItems']} items"
+    end
+    return problems unless schema['items']
+
+    problems + value.each_with_index.flat_map do |item, n|
+      errors(item, schema['items'], root, "#{path}[#{n}]")
+    end
+  end
+end
+
+if $PROGRAM_NAME == __FILE__
+  root = File.expand_path('..', __dir__)
+  problems = ExportSchemas.check(ARGV[0] || File.join(root, 'build'),
+                                 File.join(root, 'source', 'results'))
+  puts problems
+  exit(problems.empty? ? 0 : 1)
+end
@@ -0,0 +1,37 @@
+#!/bin/sh
+
+# Checks the signatures written by a build with SIGNING_KEY set: that
+# SHA256SUMS is signed by the given Ed25519 public key (PEM), that every file it
+# lists still has that hash, and that each file's own .sig is valid. Needs
+# OpenSSL 3 for Ed25519 signatures of whole files. Exits non-zero if anything
+# doesn't match, e.g.
+#
+#   scripts/verify-signatures.sh signing.pub.pem build/results
+
+if [ $# -ne 2 ]
+then
+  echo "usage: $0 PUBLIC_KEY RESULTS_DIR" >&2
+  exit 2
+fi
+key="$1"
+dir="$2"
+status=0
+
+verify() {
+  if ! openssl pkeyutl -verify -pubin -inkey "$key" -rawin \
+       -in "$dir/$1" -sigfile "$dir/$1.sig" > /dev/null 2>&1
+  then
+    echo "bad signature: $1"
+    status=1
+  fi
+}
+
+verify SHA256SUMS
+[ $status -eq 0 ] || exit 1
+(cd "$dir" && sha256sum --quiet -c SHA256SUMS) || status=1
+cut -d ' ' -f 3- "$dir/SHA256SUMS" | while read -r file
+do
+  verify "$file"
+  [ $status -eq 0 ] || exit 1
+done || status=1
+exit $status